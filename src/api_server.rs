@@ -1,41 +1,197 @@
 use crate::chain::Coordinate;
-use crate::config::Config;
-use crate::monitor::{MonitorEvent, State};
-use crate::node::Status;
+use crate::config::{redact_url, validate_id, validate_url, EndpointDescription, NodeLabels};
+use crate::metrics::MetricsWriter;
+use crate::monitor::{self, MonitorEvent, State};
+use crate::node::{
+    ErrorRecord, ErrorSummary, ExecutionHeadCheck, ExecutionStatus, Node, NodeIdentity, PeerCount,
+    Status,
+};
+use crate::reorgs::ReorgFilter;
+use crate::store::{FinalityRecord, HeadRecord, StatusRecord};
+use eth2::types::{Hash256, Slot};
 use futures::{SinkExt, StreamExt};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use warp::filters::ws::Message;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use warp::filters::ws::{Message, WebSocket};
+use warp::http::StatusCode;
 use warp::Filter;
 
+// number of the latest heads of a node returned with its details
+const RECENT_HEADS_IN_DETAIL: usize = 32;
+
 #[derive(Serialize)]
 struct NodeResponse {
-    id: Option<u64>,
+    id: u64,
+    // only the scheme, host and port as the full URL may carry credentials
+    endpoint: String,
     head: Option<Coordinate>,
     version: Option<String>,
     execution_client: Option<String>,
+    execution_type: Option<String>,
+    execution: Option<ExecutionStatus>,
+    execution_head: Option<ExecutionHeadCheck>,
+    status: Status,
+    // whether the node is synced and can attest
     healthy: bool,
     syncing: bool,
+    head_slot: Option<Slot>,
+    sync_distance: Option<Slot>,
+    paused: bool,
+    reconnect_attempts: u64,
+    last_error: Option<String>,
+    errors: ErrorSummary,
+    consensus_client: Option<String>,
+    #[serde(flatten)]
+    labels: NodeLabels,
+    identity: Option<NodeIdentity>,
 }
 
-#[derive(Serialize, Clone)]
-struct NetworkConfigResponse {
-    network_name: String,
-    seconds_per_slot: u64,
-    genesis_time: u64,
-    slots_per_epoch: u64,
+impl From<&Node> for NodeResponse {
+    fn from(node: &Node) -> Self {
+        let id = node.id;
+        let endpoint = redact_url(&node.endpoint);
+        let node = node.state.lock().expect("can read");
+        Self {
+            id,
+            endpoint,
+            head: node.head,
+            version: node.version.clone(),
+            execution_client: node.execution_version.clone(),
+            execution_type: node.execution_node_type.as_ref().map(|t| t.to_string()),
+            execution: node.execution_status.clone(),
+            execution_head: node.execution_head.clone(),
+            status: node.status.clone(),
+            healthy: matches!(node.status, Status::Healthy),
+            syncing: matches!(node.status, Status::Syncing),
+            head_slot: node.head_slot,
+            sync_distance: node.sync_distance,
+            paused: node.paused,
+            reconnect_attempts: node.reconnect_attempts,
+            last_error: node.last_error.clone(),
+            errors: node.error_summary(),
+            consensus_client: node.node_type.as_ref().map(|t| t.to_string()),
+            labels: node.labels.clone(),
+            identity: node.identity.clone(),
+        }
+    }
 }
 
-impl From<&Config> for NetworkConfigResponse {
-    fn from(config: &Config) -> Self {
-        Self {
-            network_name: config.network.name.clone(),
-            seconds_per_slot: config.consensus_chain.seconds_per_slot,
-            genesis_time: config.consensus_chain.genesis_time,
-            slots_per_epoch: config.consensus_chain.slots_per_epoch,
+/// How long after the first other node to see each recent head a node saw it.
+#[derive(Serialize)]
+struct HeadLatency {
+    // recent heads of the node that other nodes have also seen
+    compared_heads: usize,
+    // heads the node saw before any other node
+    seen_first: usize,
+    mean_delay_ms: u64,
+    max_delay_ms: u64,
+}
+
+fn head_latency(node: &Node, nodes: &[Arc<Node>]) -> Option<HeadLatency> {
+    let mut earliest_arrivals = HashMap::<Hash256, u64>::new();
+    for other in nodes.iter().filter(|other| other.id != node.id) {
+        for (root, arrival) in other.head_arrivals() {
+            earliest_arrivals
+                .entry(root)
+                .and_modify(|earliest| *earliest = (*earliest).min(arrival))
+                .or_insert(arrival);
+        }
+    }
+    let delays = node
+        .head_arrivals()
+        .into_iter()
+        .filter_map(|(root, arrival)| {
+            earliest_arrivals
+                .get(&root)
+                .map(|earliest| arrival.saturating_sub(*earliest))
+        })
+        .collect::<Vec<_>>();
+    if delays.is_empty() {
+        return None;
+    }
+    Some(HeadLatency {
+        compared_heads: delays.len(),
+        seen_first: delays.iter().filter(|delay| **delay == 0).count(),
+        mean_delay_ms: delays.iter().sum::<u64>() / delays.len() as u64,
+        max_delay_ms: delays.iter().copied().max().unwrap_or_default(),
+    })
+}
+
+#[derive(Serialize)]
+struct NodeDetailResponse {
+    #[serde(flatten)]
+    node: NodeResponse,
+    peer_count: Option<PeerCount>,
+    recent_heads: Vec<HeadRecord>,
+    status_history: Vec<StatusRecord>,
+    finality_history: Vec<FinalityRecord>,
+    error_history: Vec<ErrorRecord>,
+    head_latency: Option<HeadLatency>,
+}
+
+impl NodeResponse {
+    // look up `field` by name, falling back to the tag with that key
+    fn field(&self, field: &str) -> Option<&str> {
+        match field {
+            "name" => self.labels.name.as_deref(),
+            "operator" => self.labels.operator.as_deref(),
+            "region" => self.labels.region.as_deref(),
+            "client" => self.consensus_client.as_deref(),
+            tag => self.labels.tags.get(tag).map(|value| value.as_str()),
         }
     }
+
+    fn matches(&self, query: &NodesQuery) -> bool {
+        let filters = [
+            ("name", &query.name),
+            ("operator", &query.operator),
+            ("region", &query.region),
+            ("client", &query.client),
+        ];
+        let matches_filters = filters.iter().all(|(field, expected)| match expected {
+            Some(expected) => self
+                .field(field)
+                .map(|value| value.eq_ignore_ascii_case(expected))
+                .unwrap_or(false),
+            None => true,
+        });
+        let matches_tag = match query.tag {
+            Some(ref tag) => match tag.split_once(':') {
+                Some((key, expected)) => {
+                    self.labels.tags.get(key).map(String::as_str) == Some(expected)
+                }
+                None => self.labels.tags.contains_key(tag),
+            },
+            None => true,
+        };
+        matches_filters && matches_tag
+    }
+}
+
+#[derive(Deserialize)]
+struct NodesQuery {
+    name: Option<String>,
+    operator: Option<String>,
+    region: Option<String>,
+    client: Option<String>,
+    // either `key` to require the tag or `key:value` to also require its value
+    tag: Option<String>,
+    // any of `name`, `operator`, `region`, `client` or the key of a tag
+    group_by: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AdminResponse {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct HeadsQuery {
+    start_slot: Option<u64>,
+    end_slot: Option<u64>,
 }
 
 pub struct APIServer {
@@ -58,69 +214,64 @@ impl APIServer {
     }
 
     pub async fn run(&self, addr: impl Into<SocketAddr>) {
+        warp::serve(self.routes()).run(addr).await
+    }
+
+    pub fn routes(
+        &self,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
         let state = self.state.clone();
 
         let network_config = get!("network-config", serve_network_config, state);
-        let nodes = get!("nodes", get_nodes, state);
-        // let chain = get!("chain", get_chain_data, state);
-        // // let fork_choice = get!("fork-choice", get_fork_choice, state);
+        let config_mismatches = get!("config-mismatches", get_config_mismatches, state);
+        let nodes = warp::get()
+            .and(warp::path("nodes"))
+            .and(warp::path::end())
+            .and(with_state(state.clone()))
+            .and(warp::query::<NodesQuery>())
+            .and_then(get_nodes);
+        let chain = get!("chain", get_chain_data, state);
+        let fork_choice = get!("fork-choice", get_fork_choice, state);
+        let node = warp::get()
+            .and(warp::path!("nodes" / u64))
+            .and(with_state(state.clone()))
+            .and_then(get_node);
+        let node_heads = warp::get()
+            .and(warp::path!("nodes" / u64 / "heads"))
+            .and(with_state(state.clone()))
+            .and(warp::query::<HeadsQuery>())
+            .and_then(get_node_heads);
+        let node_statuses = warp::get()
+            .and(warp::path!("nodes" / u64 / "statuses"))
+            .and(with_state(state.clone()))
+            .and_then(get_node_statuses);
+        let node_errors = warp::get()
+            .and(warp::path!("nodes" / u64 / "errors"))
+            .and(with_state(state.clone()))
+            .and_then(get_node_errors);
+        let reorgs = warp::get()
+            .and(warp::path("reorgs"))
+            .and(warp::path::end())
+            .and(with_state(state.clone()))
+            .and(warp::query::<ReorgFilter>())
+            .and_then(get_reorgs);
         // let participation = get!("participation", serve_participation_data, state);
         // let deposit_contract = get!("deposit-contract", serve_deposit_contract_data, state);
         // let weak_subjectivity = get!("weak-subjectivity", serve_weak_subjectivity_data, state);
         let connect = warp::path("connect")
+            .and(warp::path::end())
             .and(with_state(state.clone()))
             .and(warp::ws())
             .map(|state: Arc<State>, ws: warp::ws::Ws| {
-                let mut rx = state.events_tx.subscribe();
-                ws.on_upgrade(|mut socket| async move {
-                    loop {
-                        tokio::select! {
-                            result = rx.recv() => {
-                                match result {
-                                    Ok(event) => {
-                                        match event {
-                                            head @ MonitorEvent::NewHead { .. } => {
-                                                match serde_json::to_string(&head) {
-                                                    Ok(msg) => {
-                                                        let msg = Message::text(msg);
-                                                        match socket.send(msg).await {
-                                                            Ok(_) => {}
-                                                            Err(err) => log::warn!(
-                                                                "error sending ws message to client: {:?}",
-                                                                err
-                                                            ),
-                                                        }
-                                                    }
-                                                    Err(err) => {
-                                                        log::warn!("error serializing head update: {:?}", err);
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    Err(err) => {
-                                        log::warn!("error receiving update: {:?}", err);
-                                    }
-                                }
-                            }
-                            msg = socket.next() => {
-                                match msg {
-                                    Some(Ok(msg)) => {
-                                        if msg.is_close() {
-                                            log::debug!("ws client disconnecting");
-                                            break;
-                                        }
-                                    }
-                                    Some(Err(err)) => {
-                                        log::warn!("error receiving ws message from client: {:?}", err);
-                                        break;
-                                    }
-                                    None => break,
-                                }
-                            }
-                        }
-                    }
-                })
+                let rx = state.events_tx.subscribe();
+                ws.on_upgrade(|socket| forward_events(socket, rx))
+            });
+        let connect_gossip = warp::path!("connect" / "gossip")
+            .and(with_state(state.clone()))
+            .and(warp::ws())
+            .map(|state: Arc<State>, ws: warp::ws::Ws| {
+                let rx = state.gossip_events_tx.subscribe();
+                ws.on_upgrade(|socket| forward_events(socket, rx))
             });
 
         let api = warp::get()
@@ -129,20 +280,117 @@ impl APIServer {
             .and(
                 network_config
                     .or(nodes)
-                    .or(connect)
-                    // .or(chain)
-                    // .or(fork_choice)
+                    .or(node)
+                    .or(node_heads)
+                    .or(node_statuses)
+                    .or(node_errors)
+                    .or(chain)
+                    .or(fork_choice)
+                    .or(reorgs)
+                    .or(config_mismatches)
                     // .or(participation)
                     // .or(deposit_contract)
-                    // .or(weak_subjectivity),
+                    // .or(weak_subjectivity)
+                    .or(connect)
+                    .or(connect_gossip),
             );
 
+        let add_node = warp::post()
+            .and(admin_nodes_path())
+            .and(warp::path::end())
+            .and(with_state(state.clone()))
+            .and(with_authorization())
+            .and(warp::body::json())
+            .and_then(post_admin_node);
+        let remove_node = warp::delete()
+            .and(admin_nodes_path())
+            .and(warp::path!(u64))
+            .and(with_state(state.clone()))
+            .and(with_authorization())
+            .and_then(delete_admin_node);
+        let pause_node = warp::post()
+            .and(admin_nodes_path())
+            .and(warp::path!(u64 / "pause"))
+            .and(with_state(state.clone()))
+            .and(with_authorization())
+            .and_then(pause_admin_node);
+        let resume_node = warp::post()
+            .and(admin_nodes_path())
+            .and(warp::path!(u64 / "resume"))
+            .and(with_state(state.clone()))
+            .and(with_authorization())
+            .and_then(resume_admin_node);
+        let relabel_node = warp::post()
+            .and(admin_nodes_path())
+            .and(warp::path!(u64 / "labels"))
+            .and(with_state(state.clone()))
+            .and(with_authorization())
+            .and(warp::body::json())
+            .and_then(relabel_admin_node);
+        let admin = add_node
+            .or(remove_node)
+            .or(pause_node)
+            .or(resume_node)
+            .or(relabel_node);
+
+        let metrics = warp::get()
+            .and(warp::path("metrics"))
+            .and(warp::path::end())
+            .and(with_state(state.clone()))
+            .and_then(serve_metrics);
+
         let html_dir = state.config.monitor.output_dir.clone();
         let app = warp::get().and(warp::any()).and(warp::fs::dir(html_dir));
 
-        let routes = api.or(app);
+        admin.or(api).or(metrics).or(app)
+    }
+}
 
-        warp::serve(routes).run(addr).await
+// send each event on `rx` to the websocket client until it disconnects
+async fn forward_events(mut socket: WebSocket, mut rx: Receiver<MonitorEvent>) {
+    loop {
+        tokio::select! {
+            result = rx.recv() => {
+                match result {
+                    Ok(event) => {
+                        match serde_json::to_string(&event) {
+                            Ok(msg) => {
+                                let msg = Message::text(msg);
+                                match socket.send(msg).await {
+                                    Ok(_) => {}
+                                    Err(err) => log::warn!(
+                                        "error sending ws message to client: {:?}",
+                                        err
+                                    ),
+                                }
+                            }
+                            Err(err) => {
+                                log::warn!("error serializing update: {:?}", err);
+                            }
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::debug!("ws client fell behind and skipped {} updates", skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+            msg = socket.next() => {
+                match msg {
+                    Some(Ok(msg)) => {
+                        if msg.is_close() {
+                            log::debug!("ws client disconnecting");
+                            break;
+                        }
+                    }
+                    Some(Err(err)) => {
+                        log::warn!("error receiving ws message from client: {:?}", err);
+                        break;
+                    }
+                    None => break,
+                }
+            }
+        }
     }
 }
 
@@ -152,41 +400,405 @@ fn with_state(
     warp::any().map(move || state.clone())
 }
 
-async fn get_nodes(state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
+fn admin_nodes_path() -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("admin"))
+        .and(warp::path("nodes"))
+}
+
+fn with_authorization() -> impl Filter<Extract = (Option<String>,), Error = warp::Rejection> + Clone
+{
+    warp::header::optional::<String>("authorization")
+}
+
+// the admin API is only available if a token is configured and must be given as a bearer token
+fn check_authorization(state: &State, authorization: Option<String>) -> Result<(), StatusCode> {
+    let token = match state.config.monitor.admin_token {
+        Some(ref token) => token,
+        None => return Err(StatusCode::NOT_FOUND),
+    };
+    match authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        Some(given) if constant_time_eq(given.as_bytes(), token.as_bytes()) => Ok(()),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+// compare without returning early so the time taken does not reveal how much of the token matched
+fn constant_time_eq(given: &[u8], expected: &[u8]) -> bool {
+    if given.len() != expected.len() {
+        return false;
+    }
+    given
+        .iter()
+        .zip(expected)
+        .fold(0u8, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
+
+fn admin_reply(
+    message: impl Into<String>,
+    status: StatusCode,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    let response = AdminResponse {
+        message: message.into(),
+    };
+    warp::reply::with_status(warp::reply::json(&response), status)
+}
+
+fn unauthorized_reply(status: StatusCode) -> warp::reply::WithStatus<warp::reply::Json> {
+    let message = if status == StatusCode::NOT_FOUND {
+        "the admin API is disabled"
+    } else {
+        "missing or invalid admin token"
+    };
+    admin_reply(message, status)
+}
+
+async fn post_admin_node(
+    state: Arc<State>,
+    authorization: Option<String>,
+    description: EndpointDescription,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(status) = check_authorization(&state, authorization) {
+        return Ok(unauthorized_reply(status));
+    }
+    if let Err(err) = validate_url("consensus".to_string(), &description.consensus) {
+        return Ok(admin_reply(err.to_string(), StatusCode::BAD_REQUEST));
+    }
+    if let Some(ref execution) = description.execution {
+        if let Err(err) = validate_url("execution".to_string(), execution) {
+            return Ok(admin_reply(err.to_string(), StatusCode::BAD_REQUEST));
+        }
+    }
+    if let Some(id) = description.id {
+        if let Err(err) = validate_id("id".to_string(), id) {
+            return Ok(admin_reply(err.to_string(), StatusCode::BAD_REQUEST));
+        }
+    }
+    let id = match monitor::add_node(&state, &description) {
+        Some(id) => id,
+        None => {
+            let message = format!(
+                "node at {} or with the same id is already monitored",
+                description.consensus
+            );
+            return Ok(admin_reply(message, StatusCode::CONFLICT));
+        }
+    };
+    let message = format!(
+        "started monitoring node at {} with id {}",
+        description.consensus, id
+    );
+    Ok(admin_reply(message, StatusCode::CREATED))
+}
+
+fn unknown_node_reply(id: u64) -> warp::reply::WithStatus<warp::reply::Json> {
+    let message = format!("no node with id {} is monitored", id);
+    admin_reply(message, StatusCode::NOT_FOUND)
+}
+
+async fn delete_admin_node(
+    id: u64,
+    state: Arc<State>,
+    authorization: Option<String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(status) = check_authorization(&state, authorization) {
+        return Ok(unauthorized_reply(status));
+    }
+    if !monitor::remove_node(&state, id) {
+        return Ok(unknown_node_reply(id));
+    }
+    let message = format!("stopped monitoring node {}", id);
+    Ok(admin_reply(message, StatusCode::OK))
+}
+
+async fn pause_admin_node(
+    id: u64,
+    state: Arc<State>,
+    authorization: Option<String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(status) = check_authorization(&state, authorization) {
+        return Ok(unauthorized_reply(status));
+    }
+    if !monitor::set_node_paused(&state, id, true) {
+        return Ok(unknown_node_reply(id));
+    }
+    let message = format!("paused node {}", id);
+    Ok(admin_reply(message, StatusCode::OK))
+}
+
+async fn resume_admin_node(
+    id: u64,
+    state: Arc<State>,
+    authorization: Option<String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(status) = check_authorization(&state, authorization) {
+        return Ok(unauthorized_reply(status));
+    }
+    if !monitor::set_node_paused(&state, id, false) {
+        return Ok(unknown_node_reply(id));
+    }
+    let message = format!("resumed node {}", id);
+    Ok(admin_reply(message, StatusCode::OK))
+}
+
+async fn relabel_admin_node(
+    id: u64,
+    state: Arc<State>,
+    authorization: Option<String>,
+    labels: NodeLabels,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(status) = check_authorization(&state, authorization) {
+        return Ok(unauthorized_reply(status));
+    }
+    if !monitor::set_node_labels(&state, id, labels) {
+        return Ok(unknown_node_reply(id));
+    }
+    let message = format!("updated labels of node {}", id);
+    Ok(admin_reply(message, StatusCode::OK))
+}
+
+async fn get_nodes(
+    state: Arc<State>,
+    query: NodesQuery,
+) -> Result<impl warp::Reply, warp::Rejection> {
     let nodes = state
-        .nodes
+        .nodes()
         .iter()
-        .map(|node| {
-            let node = node.state.lock().expect("can read");
-            NodeResponse {
-                id: node.id,
-                head: node.head,
-                version: node.version.clone(),
-                execution_client: node.execution_description.clone(),
-                healthy: matches!(node.status, Status::Healthy | Status::Syncing),
-                syncing: matches!(node.status, Status::Syncing),
-            }
-        })
+        .map(|node| NodeResponse::from(node.as_ref()))
+        .filter(|node| node.matches(&query))
         .collect::<Vec<_>>();
-    Ok(warp::reply::json(&nodes))
+    match query.group_by {
+        Some(ref field) => {
+            let mut groups: BTreeMap<String, Vec<NodeResponse>> = BTreeMap::new();
+            for node in nodes {
+                let group = node.field(field).unwrap_or("unknown").to_string();
+                groups.entry(group).or_default().push(node);
+            }
+            Ok(warp::reply::json(&groups))
+        }
+        None => Ok(warp::reply::json(&nodes)),
+    }
 }
 
 async fn serve_network_config(state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
-    let network_config: NetworkConfigResponse = (&state.config).into();
-    Ok(warp::reply::json(&network_config))
+    Ok(warp::reply::json(&state.spec))
 }
 
-// async fn get_chain_data(state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
-//     // let status = state.chain.get_status();
-//     let status: FinalityData = Default::default();
-//     Ok(warp::reply::json(&status))
-// }
+async fn get_config_mismatches(state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
+    let mismatches = state.config_checker.mismatches();
+    Ok(warp::reply::json(&mismatches))
+}
 
-// async fn get_fork_choice(state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
-//     let state = state.lock().expect("can read state");
-//     let tree = state.fork_choice.tree.read().expect("has data");
-//     Ok(warp::reply::json(&*tree))
-// }
+async fn get_chain_data(state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = state.chain.get_status();
+    Ok(warp::reply::json(&status))
+}
+
+async fn get_fork_choice(state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
+    let tree = state.fork_choice.tree.lock().expect("can read tree");
+    Ok(warp::reply::json(&*tree))
+}
+
+async fn get_node(id: u64, state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
+    let node = state
+        .find_node_by_id(id)
+        .ok_or_else(warp::reject::not_found)?;
+    let recent_heads = state.store.recent_heads(id, RECENT_HEADS_IN_DETAIL);
+    let peer_count = node.state.lock().expect("can read").peer_count;
+    let response = NodeDetailResponse {
+        node: NodeResponse::from(node.as_ref()),
+        peer_count,
+        recent_heads,
+        status_history: state.store.statuses(id),
+        finality_history: state.store.finality(id),
+        error_history: node.errors(),
+        head_latency: head_latency(&node, &state.nodes()),
+    };
+    Ok(warp::reply::json(&response))
+}
+
+async fn get_node_heads(
+    id: u64,
+    state: Arc<State>,
+    query: HeadsQuery,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    state
+        .find_node_by_id(id)
+        .ok_or_else(warp::reject::not_found)?;
+    let start_slot = Slot::new(query.start_slot.unwrap_or(0));
+    let end_slot = Slot::new(query.end_slot.unwrap_or(u64::MAX));
+    let heads = state.store.heads(id, start_slot, end_slot);
+    Ok(warp::reply::json(&heads))
+}
+
+async fn get_node_statuses(
+    id: u64,
+    state: Arc<State>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    state
+        .find_node_by_id(id)
+        .ok_or_else(warp::reject::not_found)?;
+    let statuses = state.store.statuses(id);
+    Ok(warp::reply::json(&statuses))
+}
+
+async fn get_node_errors(id: u64, state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
+    let node = state
+        .find_node_by_id(id)
+        .ok_or_else(warp::reject::not_found)?;
+    Ok(warp::reply::json(&node.errors()))
+}
+
+async fn get_reorgs(
+    state: Arc<State>,
+    filter: ReorgFilter,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let reorgs = state.reorgs.query(&filter);
+    Ok(warp::reply::json(&reorgs))
+}
+
+// series are labelled by the stable id rather than the endpoint, which may carry credentials
+struct NodeMetricLabels {
+    id: String,
+    name: Option<String>,
+}
+
+impl NodeMetricLabels {
+    fn as_pairs(&self) -> Vec<(&str, &str)> {
+        let mut pairs = vec![("id", self.id.as_str())];
+        if let Some(name) = &self.name {
+            pairs.push(("name", name.as_str()));
+        }
+        pairs
+    }
+}
+
+async fn serve_metrics(state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut writer = MetricsWriter::default();
+
+    // paused nodes are not queried so anything reported for them would be stale
+    let monitored_nodes = state.active_nodes();
+    let nodes = monitored_nodes
+        .iter()
+        .map(|node| {
+            let inner = node.state.lock().expect("can read");
+            let labels = NodeMetricLabels {
+                id: node.id.to_string(),
+                name: inner.labels.name.clone(),
+            };
+            (node, inner.head, inner.status.clone(), labels)
+        })
+        .collect::<Vec<_>>();
+
+    writer.describe(
+        "node_head_slot",
+        "gauge",
+        "slot of the latest head of the node",
+    );
+    for (_, head, _, labels) in &nodes {
+        if let Some(head) = head {
+            let labels = labels.as_pairs();
+            writer.sample("node_head_slot", &labels, head.slot.as_u64() as f64);
+        }
+    }
+
+    writer.describe(
+        "node_head_lag_slots",
+        "gauge",
+        "number of slots the head of the node is behind the wall clock",
+    );
+    for (_, head, _, labels) in &nodes {
+        if let Some(lag) = head.and_then(|head| state.timer.slots_behind(head.slot)) {
+            let labels = labels.as_pairs();
+            writer.sample("node_head_lag_slots", &labels, lag as f64);
+        }
+    }
+
+    writer.describe(
+        "node_syncing",
+        "gauge",
+        "1 if the node is syncing, 0 otherwise",
+    );
+    for (_, _, status, labels) in &nodes {
+        let labels = labels.as_pairs();
+        let syncing = matches!(status, Status::Syncing);
+        writer.sample("node_syncing", &labels, syncing as u8 as f64);
+    }
+
+    writer.describe(
+        "node_reachable",
+        "gauge",
+        "1 if the node is reachable, 0 otherwise",
+    );
+    for (_, _, status, labels) in &nodes {
+        let labels = labels.as_pairs();
+        let reachable = !matches!(status, Status::Unreachable);
+        writer.sample("node_reachable", &labels, reachable as u8 as f64);
+    }
+
+    writer.describe(
+        "node_sse_reconnects_total",
+        "counter",
+        "number of times the event stream of the node has been reconnected",
+    );
+    for (node, _, _, labels) in &nodes {
+        let labels = labels.as_pairs();
+        let reconnects = node.api_client.metrics().sse_reconnects();
+        writer.sample("node_sse_reconnects_total", &labels, reconnects as f64);
+    }
+
+    writer.describe(
+        "node_request_latency_seconds",
+        "histogram",
+        "latency of requests to the beacon API of the node",
+    );
+    for (node, _, _, labels) in &nodes {
+        let labels = labels.as_pairs();
+        let latency = node.api_client.metrics().request_latency();
+        writer.histogram("node_request_latency_seconds", &labels, &latency);
+    }
+
+    let distinct_heads = nodes
+        .iter()
+        .filter_map(|(_, head, _, _)| head.map(|head| head.root))
+        .collect::<HashSet<_>>()
+        .len();
+    writer.describe(
+        "monitor_distinct_heads",
+        "gauge",
+        "number of distinct heads across all monitored nodes",
+    );
+    writer.sample("monitor_distinct_heads", &[], distinct_heads as f64);
+
+    let finalized_checkpoint = state
+        .chain
+        .get_status()
+        .finality_data
+        .and_then(|data| data.finalized_checkpoint);
+    writer.describe(
+        "monitor_finalized_epoch",
+        "gauge",
+        "finalized epoch agreed upon by the majority of monitored nodes",
+    );
+    if let Some(checkpoint) = finalized_checkpoint {
+        writer.sample(
+            "monitor_finalized_epoch",
+            &[],
+            checkpoint.epoch.as_u64() as f64,
+        );
+    }
+
+    Ok(warp::reply::with_header(
+        writer.finish(),
+        "content-type",
+        "text/plain; version=0.0.4",
+    ))
+}
 
 // async fn serve_participation_data(_state: Arc<State>) -> Result<impl warp::Reply, warp::Rejection> {
 //     let response: Vec<String> = vec![];
@@ -0,0 +1,37 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const INITIAL_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(600);
+// caps the exponent so the delay cannot overflow
+const MAX_DOUBLINGS: u32 = 16;
+
+// a value in `[0, 1)` that is random enough to spread out retries
+fn jitter() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    f64::from(nanos) / 1e9
+}
+
+/// Exponential backoff with jitter between attempts to reach a node.
+#[derive(Debug, Default)]
+pub struct Backoff {
+    attempts: u32,
+}
+
+impl Backoff {
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+
+    /// Return how long to wait before the next attempt. The delay doubles with
+    /// each attempt up to `MAX_DELAY` and up to half of it is random so that
+    /// nodes that fail together do not retry together.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = INITIAL_DELAY * (1 << self.attempts.min(MAX_DOUBLINGS));
+        self.attempts = self.attempts.saturating_add(1);
+        let half = delay.min(MAX_DELAY) / 2;
+        half + half.mul_f64(jitter())
+    }
+}
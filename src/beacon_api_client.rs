@@ -1,34 +1,51 @@
-use crate::chain::Coordinate;
-use crate::node::ConsensusType;
-use base64::{self, DecodeError};
+use crate::beacon_events::{deserialize_quoted_u64, BeaconEvent, BeaconEventError, EventTopic};
+use crate::fork_choice::ProtoArray;
+use crate::metrics::ClientMetrics;
+use crate::node::{NodeIdentity, PeerCount};
+use crate::recording::Recorder;
+use base64::DecodeError;
 use eth2::types::{
-    ErrorMessage, GenericResponse, Hash256, IdentityData, Slot, SyncingData, VersionData,
+    BeaconBlockHeader, BlockHeaderData, DepositContractData, ErrorMessage, FinalityCheckpointsData,
+    Fork, GenericResponse, GenesisData, Hash256, Slot, VersionData,
 };
 use eventsource_client as sse;
 use futures::{Stream, TryStreamExt};
 use reqwest::{Client, Error as HTTPError};
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{self, Error as JSONError};
+use std::collections::HashMap;
 use std::fmt::Write;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
 
 const ACCEPT_HEADER: &str = "Accept";
 const ACCEPT_HEADER_VALUE: &str = "text/event-stream";
 
-async fn do_get<T>(client: &Client, endpoint: &str) -> Result<T, APIClientError>
+async fn fetch_body(client: &Client, endpoint: &str) -> Result<Vec<u8>, APIClientError> {
+    let response = client.get(endpoint).send().await?;
+    let body = response.bytes().await?;
+    Ok(body.to_vec())
+}
+
+fn decode_body<T>(body: &[u8]) -> Result<T, APIClientError>
 where
     T: Serialize + DeserializeOwned,
 {
-    let response = client.get(endpoint).send().await?;
-    let body = response.bytes().await?;
-    let result = serde_json::from_slice::<GenericResponse<T>>(&body).map(|resp| resp.data);
+    decode_response::<GenericResponse<T>>(body).map(|resp| resp.data)
+}
+
+fn decode_response<R>(body: &[u8]) -> Result<R, APIClientError>
+where
+    R: DeserializeOwned,
+{
+    let result = serde_json::from_slice::<R>(body);
     match result {
         Ok(result) => Ok(result),
-        Err(err) => match serde_json::from_slice::<ErrorMessage>(&body) {
+        Err(err) => match serde_json::from_slice::<ErrorMessage>(body) {
             Ok(error) => Err(APIClientError::APIError(error.message)),
-            Err(_) => match std::str::from_utf8(&body) {
+            Err(_) => match std::str::from_utf8(body) {
                 Ok(text) => {
                     log::warn!(
                         "could not deserialize as json: `{}` (length {})",
@@ -43,10 +60,65 @@ where
     }
 }
 
+#[derive(Deserialize)]
+struct ExecutionPayloadHeader {
+    block_hash: Hash256,
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    block_number: u64,
+}
+
+#[derive(Deserialize)]
+struct BlindedBlockBody {
+    // missing before the merge
+    execution_payload_header: Option<ExecutionPayloadHeader>,
+}
+
+#[derive(Deserialize)]
+struct BlindedBlockMessage {
+    body: BlindedBlockBody,
+}
+
+#[derive(Deserialize)]
+struct BlindedBlock {
+    message: BlindedBlockMessage,
+}
+
+#[derive(Deserialize)]
+struct BlindedBlockResponse {
+    #[serde(default)]
+    execution_optimistic: bool,
+    data: BlindedBlock,
+}
+
+/// Sync status of a beacon node as reported by `node/syncing`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncingData {
+    pub head_slot: Slot,
+    pub sync_distance: Slot,
+    pub is_syncing: bool,
+    // only reported by nodes after the merge
+    #[serde(default)]
+    pub is_optimistic: bool,
+    #[serde(default)]
+    pub el_offline: bool,
+}
+
+/// The execution payload of a beacon block, as far as needed to compare
+/// against the execution node.
+#[derive(Debug, Clone, Copy)]
+pub struct ExecutionPayloadSummary {
+    // `None` if the block has no execution payload
+    pub block_hash: Option<Hash256>,
+    pub block_number: Option<u64>,
+    pub execution_optimistic: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct BeaconAPIClient {
     http: Client,
     endpoint: String,
+    metrics: Arc<ClientMetrics>,
+    recorder: Option<Recorder>,
 }
 
 const ENDPOINT_PREFIX: &str = "/eth/v1/";
@@ -65,21 +137,50 @@ pub enum APIClientError {
     EventSourceError(String),
     #[error("error decoding base64 data: {0}")]
     Base64Error(#[from] DecodeError),
+    #[error("error with beacon event: {0}")]
+    EventError(#[from] BeaconEventError),
 }
 
 type APIResult<T> = Result<T, APIClientError>;
 
 impl BeaconAPIClient {
-    pub fn new(client: Client, endpoint: &str) -> Self {
+    pub fn new(client: Client, endpoint: &str, recorder: Option<Recorder>) -> Self {
         Self {
             http: client,
             endpoint: endpoint.to_string() + ENDPOINT_PREFIX,
+            metrics: Default::default(),
+            recorder,
         }
     }
 
-    // pub fn get_endpoint(&self) -> &str {
-    //     self.endpoint.trim_end_matches(ENDPOINT_PREFIX)
-    // }
+    pub fn metrics(&self) -> &ClientMetrics {
+        &self.metrics
+    }
+
+    async fn fetch(&self, endpoint: &str) -> APIResult<Vec<u8>> {
+        let start = Instant::now();
+        let body = fetch_body(&self.http, endpoint).await;
+        self.metrics.observe_request_latency(start.elapsed());
+        let body = body?;
+        if let Some(ref recorder) = self.recorder {
+            let base = self.get_endpoint();
+            let path = endpoint.trim_start_matches(base);
+            recorder.record_response(base, path, &body);
+        }
+        Ok(body)
+    }
+
+    async fn get<T>(&self, endpoint: &str) -> APIResult<T>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let body = self.fetch(endpoint).await?;
+        decode_body(&body)
+    }
+
+    pub fn get_endpoint(&self) -> &str {
+        self.endpoint.trim_end_matches(ENDPOINT_PREFIX)
+    }
 
     fn endpoint_for(&self, suffix: &str) -> String {
         let mut result = self.endpoint.clone();
@@ -89,119 +190,132 @@ impl BeaconAPIClient {
 
     pub async fn get_node_version(&self) -> APIResult<String> {
         let endpoint = self.endpoint_for("node/version");
-        do_get(&self.http, &endpoint)
+        self.get(&endpoint)
             .await
             .map(|data: VersionData| data.version)
     }
 
     // pub async fn get_latest_header(&self) -> APIResult<(Hash256, BlockHeaderAndSignature)> {
     //     let endpoint = self.endpoint_for("beacon/headers/head");
-    //     do_get(&self.http, &endpoint)
+    //     self.get(&endpoint)
     //         .await
     //         .map(|data: BlockHeaderData| (data.root, data.header))
     // }
 
+    pub async fn get_block_header(&self, root: Hash256) -> APIResult<BeaconBlockHeader> {
+        let endpoint = self.endpoint_for(&format!("beacon/headers/{:?}", root));
+        self.get(&endpoint)
+            .await
+            .map(|data: BlockHeaderData| data.header.message)
+    }
+
+    pub async fn get_execution_payload_summary(
+        &self,
+        root: Hash256,
+    ) -> APIResult<ExecutionPayloadSummary> {
+        let endpoint = self.endpoint_for(&format!("beacon/blinded_blocks/{:?}", root));
+        let body = self.fetch(&endpoint).await?;
+        let response: BlindedBlockResponse = decode_response(&body)?;
+        let header = response.data.message.body.execution_payload_header;
+        Ok(ExecutionPayloadSummary {
+            block_hash: header.as_ref().map(|header| header.block_hash),
+            block_number: header.as_ref().map(|header| header.block_number),
+            execution_optimistic: response.execution_optimistic,
+        })
+    }
+
+    pub async fn get_genesis(&self) -> APIResult<GenesisData> {
+        let endpoint = self.endpoint_for("beacon/genesis");
+        self.get(&endpoint).await
+    }
+
+    pub async fn get_spec(&self) -> APIResult<HashMap<String, serde_json::Value>> {
+        let endpoint = self.endpoint_for("config/spec");
+        self.get(&endpoint).await
+    }
+
+    pub async fn get_fork_schedule(&self) -> APIResult<Vec<Fork>> {
+        let endpoint = self.endpoint_for("config/fork_schedule");
+        self.get(&endpoint).await
+    }
+
+    pub async fn get_deposit_contract(&self) -> APIResult<DepositContractData> {
+        let endpoint = self.endpoint_for("config/deposit_contract");
+        self.get(&endpoint).await
+    }
+
     pub async fn get_sync_status(&self) -> APIResult<SyncingData> {
         let endpoint = self.endpoint_for("node/syncing");
-        do_get(&self.http, &endpoint).await
+        self.get(&endpoint).await
     }
 
-    pub async fn get_identity_data(&self) -> APIResult<IdentityData> {
+    pub async fn get_identity_data(&self) -> APIResult<NodeIdentity> {
         let endpoint = self.endpoint_for("node/identity");
-        do_get(&self.http, &endpoint).await
+        self.get(&endpoint).await
     }
 
-    // pub async fn get_finality_checkpoints(&self, slot: Slot) -> APIResult<FinalityCheckpointsData> {
-    //     let endpoint_query =
-    //         String::from("beacon/states/") + &slot.to_string() + "/finality_checkpoints";
-    //     let endpoint = self.endpoint_for(&endpoint_query);
-    //     do_get(&self.http, &endpoint).await
-    // }
+    pub async fn get_peer_count(&self) -> APIResult<PeerCount> {
+        let endpoint = self.endpoint_for("node/peer_count");
+        self.get(&endpoint).await
+    }
 
-    // pub async fn get_lighthouse_fork_choice(&self) -> APIResult<ProtoArray> {
-    //     let endpoint = String::from(self.get_endpoint()) + "/lighthouse/proto_array";
-    //     do_get(&self.http, &endpoint).await
-    // }
+    pub async fn get_finality_checkpoints(
+        &self,
+        state_id: &str,
+    ) -> APIResult<FinalityCheckpointsData> {
+        let endpoint_query = String::from("beacon/states/") + state_id + "/finality_checkpoints";
+        let endpoint = self.endpoint_for(&endpoint_query);
+        self.get(&endpoint).await
+    }
+
+    pub async fn get_lighthouse_fork_choice(&self) -> APIResult<ProtoArray> {
+        let endpoint = String::from(self.get_endpoint()) + "/lighthouse/proto_array";
+        self.get(&endpoint).await
+    }
 
-    pub fn stream_head(
+    pub fn stream_events(
         &self,
-        consensus_type: ConsensusType,
-    ) -> impl Stream<Item = APIResult<APIResult<Coordinate>>> {
-        let url = self.endpoint_for("events?topics=head");
+        topics: &[EventTopic],
+    ) -> impl Stream<Item = APIResult<APIResult<BeaconEvent>>> {
+        let topics = topics
+            .iter()
+            .map(|topic| topic.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let url = self.endpoint_for(&format!("events?topics={}", topics));
         let sse_client = sse::Client::for_url(&url)
             .expect("can parse url")
-            .reconnect(
-                sse::ReconnectOptions::reconnect(true)
-                    .retry_initial(false)
-                    .delay(Duration::from_secs(5))
-                    .backoff_factor(2)
-                    .delay_max(Duration::from_secs(60))
-                    .build(),
-            )
+            // the monitor restarts a failed stream itself, with its own backoff
+            .reconnect(sse::ReconnectOptions::reconnect(false).build())
             .header(ACCEPT_HEADER, ACCEPT_HEADER_VALUE)
             .expect("can add header")
             .build();
-        parse_head_events(sse_client, consensus_type)
+        let recorder = self
+            .recorder
+            .clone()
+            .map(|recorder| (recorder, self.get_endpoint().to_string()));
+        parse_events(sse_client, recorder)
     }
 }
 
-fn parse_head_events(
+fn parse_events(
     client: sse::Client<sse::HttpsConnector>,
-    consensus_type: ConsensusType,
-) -> impl Stream<Item = APIResult<APIResult<Coordinate>>> {
+    recorder: Option<(Recorder, String)>,
+) -> impl Stream<Item = APIResult<APIResult<BeaconEvent>>> {
     client
         .stream()
         .map_ok(move |event| {
             let event_type = event.event_type.trim();
-            assert!(event_type == "head");
-            let data = event.field("data");
-            if let Some(data) = data {
-                let json: serde_json::Value = serde_json::from_slice(data).unwrap();
-                match json {
-                    serde_json::Value::Object(data) => {
-                        let root = data.get("block").ok_or_else(|| {
-                            APIClientError::APIError("missing block root from head API".to_string())
-                        })?;
-                        let root: Hash256 = match root {
-                            serde_json::Value::String(data) => match consensus_type {
-                                ConsensusType::Prysm => base64::decode(&data)
-                                    .map(|root| Hash256::from_slice(&root))
-                                    .map_err(|p| p.into()),
-                                _ => (&data[2..]).parse::<Hash256>().map_err(|err| {
-                                    let mut buffer = String::new();
-                                    let _ = write!(&mut buffer, "{:?}", err);
-                                    APIClientError::EventSourceError(buffer)
-                                }),
-                            },
-                            _ => Err(APIClientError::APIError(
-                                "wrong type for field in head event API".to_string(),
-                            )),
-                        }?;
-                        let slot = data.get("slot").ok_or_else(|| {
-                            APIClientError::APIError("missing slot from head event API".to_string())
-                        })?;
-                        let slot = match slot {
-                            serde_json::Value::String(data) => {
-                                data.parse::<Slot>().map_err(|err| {
-                                    let mut buffer = String::new();
-                                    let _ = write!(&mut buffer, "{:?}", err);
-                                    APIClientError::EventSourceError(buffer)
-                                })
-                            }
-                            _ => Err(APIClientError::APIError(
-                                "wrong type for field in head event API".to_string(),
-                            )),
-                        }?;
-                        Ok(Coordinate { slot, root })
+            match event.field("data") {
+                Some(data) => {
+                    if let Some((ref recorder, ref endpoint)) = recorder {
+                        recorder.record_event(endpoint, event_type, data);
                     }
-                    _ => Err(APIClientError::APIError(
-                        "json from head stream did not match expected format".to_string(),
-                    )),
+                    BeaconEvent::decode(event_type, data).map_err(|err| err.into())
                 }
-            } else {
-                Err(APIClientError::APIError(
+                None => Err(APIClientError::APIError(
                     "expected API response is malformed".to_string(),
-                ))
+                )),
             }
         })
         .map_err(|err| {
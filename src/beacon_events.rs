@@ -0,0 +1,224 @@
+use crate::chain::Coordinate;
+use eth2::types::{Epoch, Hash256, Slot};
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+/// Topics of the beacon node event stream the monitor can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventTopic {
+    Head,
+    Block,
+    Attestation,
+    VoluntaryExit,
+    FinalizedCheckpoint,
+    ChainReorg,
+    ContributionAndProof,
+}
+
+impl fmt::Display for EventTopic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventTopic::Head => write!(f, "head"),
+            EventTopic::Block => write!(f, "block"),
+            EventTopic::Attestation => write!(f, "attestation"),
+            EventTopic::VoluntaryExit => write!(f, "voluntary_exit"),
+            EventTopic::FinalizedCheckpoint => write!(f, "finalized_checkpoint"),
+            EventTopic::ChainReorg => write!(f, "chain_reorg"),
+            EventTopic::ContributionAndProof => write!(f, "contribution_and_proof"),
+        }
+    }
+}
+
+// NOTE: some clients (e.g. Prysm) encode roots as base64 rather than hex
+fn deserialize_root<'de, D>(deserializer: D) -> Result<Hash256, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let data = String::deserialize(deserializer)?;
+    let bytes = match data.strip_prefix("0x") {
+        Some(hex) => hex_to_bytes(hex).map_err(de::Error::custom)?,
+        None => base64::decode(&data).map_err(de::Error::custom)?,
+    };
+    if bytes.len() != Hash256::len_bytes() {
+        return Err(de::Error::custom(format!(
+            "root has {} bytes but expected {}",
+            bytes.len(),
+            Hash256::len_bytes()
+        )));
+    }
+    Ok(Hash256::from_slice(&bytes))
+}
+
+fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, String> {
+    if hex.len() % 2 != 0 {
+        return Err(format!("hex string has odd length: {}", hex));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|err| format!("invalid hex string {}: {}", hex, err))
+        })
+        .collect()
+}
+
+pub(crate) fn deserialize_quoted_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let data = String::deserialize(deserializer)?;
+    data.parse().map_err(de::Error::custom)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadEvent {
+    pub slot: Slot,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub block: Hash256,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub state: Hash256,
+    #[serde(default)]
+    pub epoch_transition: bool,
+}
+
+impl From<&HeadEvent> for Coordinate {
+    fn from(event: &HeadEvent) -> Self {
+        Self {
+            slot: event.slot,
+            root: event.block,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockEvent {
+    pub slot: Slot,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub block: Hash256,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointData {
+    pub epoch: Epoch,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub root: Hash256,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttestationData {
+    pub slot: Slot,
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    pub index: u64,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub beacon_block_root: Hash256,
+    pub source: CheckpointData,
+    pub target: CheckpointData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttestationEvent {
+    pub aggregation_bits: String,
+    pub data: AttestationData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoluntaryExit {
+    pub epoch: Epoch,
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    pub validator_index: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoluntaryExitEvent {
+    pub message: VoluntaryExit,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinalizedCheckpointEvent {
+    #[serde(deserialize_with = "deserialize_root")]
+    pub block: Hash256,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub state: Hash256,
+    pub epoch: Epoch,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainReorgEvent {
+    pub slot: Slot,
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    pub depth: u64,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub old_head_block: Hash256,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub new_head_block: Hash256,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub old_head_state: Hash256,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub new_head_state: Hash256,
+    pub epoch: Epoch,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncCommitteeContribution {
+    pub slot: Slot,
+    #[serde(deserialize_with = "deserialize_root")]
+    pub beacon_block_root: Hash256,
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    pub subcommittee_index: u64,
+    pub aggregation_bits: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContributionAndProof {
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    pub aggregator_index: u64,
+    pub contribution: SyncCommitteeContribution,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContributionAndProofEvent {
+    pub message: ContributionAndProof,
+}
+
+/// An event emitted on the event stream of a beacon node.
+#[derive(Debug, Clone)]
+pub enum BeaconEvent {
+    Head(HeadEvent),
+    Block(BlockEvent),
+    Attestation(Box<AttestationEvent>),
+    VoluntaryExit(VoluntaryExitEvent),
+    FinalizedCheckpoint(FinalizedCheckpointEvent),
+    ChainReorg(ChainReorgEvent),
+    ContributionAndProof(Box<ContributionAndProofEvent>),
+}
+
+#[derive(Error, Debug)]
+pub enum BeaconEventError {
+    #[error("unknown event type `{0}` in event stream")]
+    UnknownEventType(String),
+    #[error("could not decode event: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl BeaconEvent {
+    pub fn decode(event_type: &str, data: &[u8]) -> Result<Self, BeaconEventError> {
+        let event = match event_type {
+            "head" => serde_json::from_slice(data).map(BeaconEvent::Head),
+            "block" => serde_json::from_slice(data).map(BeaconEvent::Block),
+            "attestation" => serde_json::from_slice(data).map(BeaconEvent::Attestation),
+            "voluntary_exit" => serde_json::from_slice(data).map(BeaconEvent::VoluntaryExit),
+            "finalized_checkpoint" => {
+                serde_json::from_slice(data).map(BeaconEvent::FinalizedCheckpoint)
+            }
+            "chain_reorg" => serde_json::from_slice(data).map(BeaconEvent::ChainReorg),
+            "contribution_and_proof" => {
+                serde_json::from_slice(data).map(BeaconEvent::ContributionAndProof)
+            }
+            other => return Err(BeaconEventError::UnknownEventType(other.to_string())),
+        };
+        event.map_err(BeaconEventError::Json)
+    }
+}
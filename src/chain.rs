@@ -1,10 +1,11 @@
 use eth2::types::{Checkpoint, FinalityCheckpointsData};
 use eth2::types::{Hash256, Slot};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 use std::sync::{Arc, Mutex};
 
-#[derive(Copy, Clone, Debug, Serialize, Default)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, Default)]
 pub struct Coordinate {
     pub slot: Slot,
     pub root: Hash256,
@@ -16,7 +17,7 @@ impl fmt::Display for Coordinate {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
 pub struct FinalityData {
     pub justified_checkpoint: Option<Checkpoint>,
     pub finalized_checkpoint: Option<Checkpoint>,
@@ -31,25 +32,136 @@ impl From<FinalityCheckpointsData> for FinalityData {
     }
 }
 
+#[derive(Clone, Debug, Serialize)]
+pub struct NodeFinalityData {
+    pub id: u64,
+    #[serde(flatten)]
+    pub finality_data: FinalityData,
+    // `true` if this node's finalized checkpoint differs from the majority
+    pub is_minority: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Default)]
+pub struct ChainStatus {
+    // finality data agreed upon by the majority of nodes
+    pub finality_data: Option<FinalityData>,
+    pub nodes: Vec<NodeFinalityData>,
+}
+
 #[derive(Clone, Debug, Default)]
 struct ChainInner {
-    finality_data: Option<FinalityData>,
+    finality_data: HashMap<u64, FinalityData>,
+}
+
+impl ChainInner {
+    // there is no majority if the most common finalized checkpoint is tied with another
+    fn majority_finality_data(&self) -> Option<&FinalityData> {
+        let mut tally: Vec<(&FinalityData, usize)> = vec![];
+        for vote in self.finality_data.values() {
+            match tally
+                .iter_mut()
+                .find(|(candidate, _)| candidate.finalized_checkpoint == vote.finalized_checkpoint)
+            {
+                Some((_, count)) => *count += 1,
+                None => tally.push((vote, 1)),
+            }
+        }
+        let max_count = tally.iter().map(|(_, count)| *count).max()?;
+        let mut leaders = tally.into_iter().filter(|(_, count)| *count == max_count);
+        match (leaders.next(), leaders.next()) {
+            (Some((majority, _)), None) => Some(majority),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct Chain(Arc<Mutex<ChainInner>>);
 
 impl Chain {
-    // pub fn get_status(&self) -> Option<FinalityData> {
-    //     self.0
-    //         .lock()
-    //         .ok()
-    //         .and_then(|guard| guard.finality_data.clone())
-    // }
-
-    // pub fn set_status(&self, data: FinalityData) {
-    //     if let Ok(mut inner) = self.0.lock() {
-    //         inner.finality_data = Some(data);
-    //     }
-    // }
+    pub fn get_status(&self) -> ChainStatus {
+        let inner = self.0.lock().expect("can lock chain");
+        let majority = inner.majority_finality_data().cloned();
+        let mut nodes = inner
+            .finality_data
+            .iter()
+            .map(|(id, finality_data)| {
+                let is_minority = match majority {
+                    Some(ref majority) => {
+                        majority.finalized_checkpoint != finality_data.finalized_checkpoint
+                    }
+                    None => false,
+                };
+                NodeFinalityData {
+                    id: *id,
+                    finality_data: finality_data.clone(),
+                    is_minority,
+                }
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|node| node.id);
+        ChainStatus {
+            finality_data: majority,
+            nodes,
+        }
+    }
+
+    pub fn set_status(&self, id: u64, data: FinalityData) {
+        if let Ok(mut inner) = self.0.lock() {
+            inner.finality_data.insert(id, data);
+        }
+    }
+
+    pub fn remove_status(&self, id: u64) {
+        if let Ok(mut inner) = self.0.lock() {
+            inner.finality_data.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eth2::types::Epoch;
+
+    fn finalized_at(epoch: u64) -> FinalityData {
+        FinalityData {
+            justified_checkpoint: None,
+            finalized_checkpoint: Some(Checkpoint {
+                epoch: Epoch::new(epoch),
+                root: Hash256::repeat_byte(epoch as u8),
+            }),
+        }
+    }
+
+    #[test]
+    fn flags_nodes_outside_the_majority() {
+        let chain = Chain::default();
+        chain.set_status(1, finalized_at(10));
+        chain.set_status(2, finalized_at(10));
+        chain.set_status(3, finalized_at(9));
+
+        let status = chain.get_status();
+        assert_eq!(status.finality_data, Some(finalized_at(10)));
+        let minority = status
+            .nodes
+            .iter()
+            .filter(|node| node.is_minority)
+            .map(|node| node.id)
+            .collect::<Vec<_>>();
+        assert_eq!(minority, vec![3]);
+    }
+
+    #[test]
+    fn reports_no_majority_on_a_tie() {
+        let chain = Chain::default();
+        chain.set_status(1, finalized_at(10));
+        chain.set_status(2, finalized_at(10));
+        chain.set_status(3, finalized_at(9));
+        chain.set_status(4, finalized_at(9));
+
+        let status = chain.get_status();
+        assert_eq!(status.finality_data, None);
+        assert!(status.nodes.iter().all(|node| !node.is_minority));
+    }
 }
@@ -0,0 +1,92 @@
+use futures::future::BoxFuture;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+
+/// Clock is the source of time for anything driven by slots and epochs
+/// and for the timestamps the monitor attaches to what it observes.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Return the current time as a duration since the unix epoch.
+    fn now(&self) -> Duration;
+
+    /// Wait until the clock reads at least `deadline`, given as a duration since the unix epoch.
+    fn sleep_until(&self, deadline: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// SystemClock follows the system's wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time is after unix epoch")
+    }
+
+    fn sleep_until(&self, deadline: Duration) -> BoxFuture<'static, ()> {
+        let duration = deadline.saturating_sub(self.now());
+        Box::pin(sleep(duration))
+    }
+}
+
+#[cfg(test)]
+pub use manual::ManualClock;
+
+#[cfg(test)]
+mod manual {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::Notify;
+
+    #[derive(Debug, Default)]
+    struct ManualClockInner {
+        now: Mutex<Duration>,
+        notify: Notify,
+    }
+
+    /// ManualClock only moves when told to, so that time-dependent logic can be
+    /// driven deterministically.
+    #[derive(Debug, Clone, Default)]
+    pub struct ManualClock(Arc<ManualClockInner>);
+
+    impl ManualClock {
+        pub fn new(now: Duration) -> Self {
+            let inner = ManualClockInner {
+                now: Mutex::new(now),
+                notify: Notify::new(),
+            };
+            Self(Arc::new(inner))
+        }
+
+        pub fn set(&self, now: Duration) {
+            *self.0.now.lock().expect("can lock clock") = now;
+            self.0.notify.notify_waiters();
+        }
+
+        pub fn advance(&self, duration: Duration) {
+            let now = self.now();
+            self.set(now + duration);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Duration {
+            *self.0.now.lock().expect("can lock clock")
+        }
+
+        fn sleep_until(&self, deadline: Duration) -> BoxFuture<'static, ()> {
+            let clock = self.clone();
+            Box::pin(async move {
+                loop {
+                    // register for updates before reading the time so none are missed
+                    let notified = clock.0.notify.notified();
+                    if clock.now() >= deadline {
+                        return;
+                    }
+                    notified.await;
+                }
+            })
+        }
+    }
+}
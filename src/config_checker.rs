@@ -0,0 +1,276 @@
+use crate::config::Spec;
+use eth2::types::{DepositContractData, Fork};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+fn format_version(version: &[u8; 4]) -> String {
+    let hex = version
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!("0x{}", hex)
+}
+
+/// The configuration of the network as reported by a single node.
+#[derive(Debug, Clone)]
+pub struct NodeNetworkConfig {
+    pub spec: HashMap<String, serde_json::Value>,
+    pub fork_schedule: Vec<Fork>,
+    pub deposit_contract: DepositContractData,
+}
+
+impl NodeNetworkConfig {
+    // flatten into one value per field so configs can be compared field by field
+    fn into_fields(self) -> BTreeMap<String, String> {
+        let mut fields = BTreeMap::new();
+        for (key, value) in self.spec {
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            };
+            // clients differ in the casing of hex values
+            let value = if value.starts_with("0x") {
+                value.to_lowercase()
+            } else {
+                value
+            };
+            fields.insert(format!("spec.{}", key), value);
+        }
+        for (i, fork) in self.fork_schedule.iter().enumerate() {
+            let value = format!(
+                "{} -> {} at epoch {}",
+                format_version(&fork.previous_version),
+                format_version(&fork.current_version),
+                fork.epoch
+            );
+            fields.insert(format!("fork_schedule.{}", i), value);
+        }
+        fields.insert(
+            "deposit_contract.address".to_string(),
+            format!("{:?}", self.deposit_contract.address),
+        );
+        fields.insert(
+            "deposit_contract.chain_id".to_string(),
+            self.deposit_contract.chain_id.to_string(),
+        );
+        fields
+    }
+}
+
+/// A value of some field and the nodes that report it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigValue {
+    pub value: String,
+    pub node_ids: Vec<u64>,
+}
+
+/// A field of the network configuration that the nodes disagree on,
+/// either with each other or with the value `expected` by the config.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigMismatch {
+    pub field: String,
+    pub expected: Option<String>,
+    pub values: Vec<ConfigValue>,
+}
+
+pub enum ConfigUpdate {
+    Detected(Vec<ConfigMismatch>),
+    Resolved,
+}
+
+#[derive(Debug, Default)]
+struct ConfigCheckerInner {
+    configs: HashMap<u64, BTreeMap<String, String>>,
+    mismatches: Vec<ConfigMismatch>,
+}
+
+/// Compares the spec, fork schedule and deposit contract reported by
+/// each node and tracks any fields the nodes disagree on.
+#[derive(Clone, Default)]
+pub struct ConfigChecker {
+    inner: Arc<Mutex<ConfigCheckerInner>>,
+    // values of fields given in the config, e.g. from a network preset
+    expected: Arc<BTreeMap<String, String>>,
+}
+
+fn expected_fields(spec: &Spec) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    if let Some(ref address) = spec.deposit_contract_address {
+        fields.insert("deposit_contract.address".to_string(), address.clone());
+    }
+    if let Some(chain_id) = spec.deposit_chain_id {
+        fields.insert(
+            "deposit_contract.chain_id".to_string(),
+            chain_id.to_string(),
+        );
+    }
+    for (fork, epoch) in &spec.fork_epochs {
+        fields.insert(
+            format!("spec.{}_FORK_EPOCH", fork.to_uppercase()),
+            epoch.to_string(),
+        );
+    }
+    fields
+}
+
+fn find_mismatches(
+    configs: &HashMap<u64, BTreeMap<String, String>>,
+    expected: &BTreeMap<String, String>,
+) -> Vec<ConfigMismatch> {
+    if configs.is_empty() {
+        return vec![];
+    }
+    let fields = configs
+        .values()
+        .flat_map(|config| config.keys())
+        .chain(expected.keys())
+        .collect::<BTreeSet<_>>();
+    let mut node_ids = configs.keys().copied().collect::<Vec<_>>();
+    node_ids.sort_unstable();
+
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let mut values: Vec<ConfigValue> = vec![];
+            // clients expose different sets of fields so only compare the nodes reporting this one
+            for id in &node_ids {
+                let value = match configs[id].get(field) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                match values.iter_mut().find(|entry| &entry.value == value) {
+                    Some(entry) => entry.node_ids.push(*id),
+                    None => values.push(ConfigValue {
+                        value: value.clone(),
+                        node_ids: vec![*id],
+                    }),
+                }
+            }
+            if values.is_empty() {
+                return None;
+            }
+            let expected = expected.get(field).cloned();
+            let is_unexpected = expected
+                .as_ref()
+                .is_some_and(|expected| &values[0].value != expected);
+            if values.len() > 1 || is_unexpected {
+                Some(ConfigMismatch {
+                    field: field.clone(),
+                    expected,
+                    values,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+impl ConfigChecker {
+    pub fn new(spec: &Spec) -> Self {
+        Self {
+            inner: Default::default(),
+            expected: Arc::new(expected_fields(spec)),
+        }
+    }
+
+    pub fn mismatches(&self) -> Vec<ConfigMismatch> {
+        let inner = self.inner.lock().expect("can lock state");
+        inner.mismatches.clone()
+    }
+
+    /// Forget the config of the node with `id`, e.g. as it is no longer monitored,
+    /// returning any change in the set of mismatched fields.
+    pub fn remove(&self, id: u64) -> Option<ConfigUpdate> {
+        let mut inner = self.inner.lock().expect("can lock state");
+        inner.configs.remove(&id)?;
+        self.refresh(&mut inner)
+    }
+
+    /// Record the latest `configs` reported by the nodes, returning any
+    /// change in the set of mismatched fields.
+    pub fn update(&self, configs: Vec<(u64, NodeNetworkConfig)>) -> Option<ConfigUpdate> {
+        let mut inner = self.inner.lock().expect("can lock state");
+        for (id, config) in configs {
+            inner.configs.insert(id, config.into_fields());
+        }
+        self.refresh(&mut inner)
+    }
+
+    fn refresh(&self, inner: &mut ConfigCheckerInner) -> Option<ConfigUpdate> {
+        let mismatches = find_mismatches(&inner.configs, &self.expected);
+        if mismatches == inner.mismatches {
+            return None;
+        }
+        inner.mismatches = mismatches.clone();
+        if mismatches.is_empty() {
+            Some(ConfigUpdate::Resolved)
+        } else {
+            Some(ConfigUpdate::Detected(mismatches))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eth2::types::Address;
+    use serde_json::json;
+
+    fn config_with(spec: &[(&str, &str)]) -> NodeNetworkConfig {
+        NodeNetworkConfig {
+            spec: spec
+                .iter()
+                .map(|(key, value)| (key.to_string(), json!(value)))
+                .collect(),
+            fork_schedule: vec![],
+            deposit_contract: DepositContractData {
+                address: Address::zero(),
+                chain_id: 1,
+            },
+        }
+    }
+
+    #[test]
+    fn ignores_missing_fields_and_hex_casing() {
+        let checker = ConfigChecker::new(&Spec::default());
+        let configs = vec![
+            (
+                1,
+                config_with(&[("SECONDS_PER_SLOT", "12"), ("DOMAIN_DEPOSIT", "0x03000000")]),
+            ),
+            (
+                2,
+                config_with(&[
+                    ("SECONDS_PER_SLOT", "12"),
+                    ("DOMAIN_DEPOSIT", "0x03000000"),
+                    ("TERMINAL_BLOCK_HASH", "0xABCDEF"),
+                ]),
+            ),
+            (3, config_with(&[("TERMINAL_BLOCK_HASH", "0xabcdef")])),
+        ];
+        assert!(checker.update(configs).is_none());
+        assert!(checker.mismatches().is_empty());
+    }
+
+    #[test]
+    fn resolves_mismatch_when_the_node_is_removed() {
+        let checker = ConfigChecker::new(&Spec::default());
+        let configs = vec![
+            (1, config_with(&[("SECONDS_PER_SLOT", "12")])),
+            (2, config_with(&[("SECONDS_PER_SLOT", "6")])),
+        ];
+        assert!(matches!(
+            checker.update(configs),
+            Some(ConfigUpdate::Detected(_))
+        ));
+        let mismatches = checker.mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].field, "spec.SECONDS_PER_SLOT");
+        assert_eq!(mismatches[0].values.len(), 2);
+
+        assert!(matches!(checker.remove(2), Some(ConfigUpdate::Resolved)));
+        assert!(checker.mismatches().is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+use futures::future;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::time::sleep;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+async fn recv_hangup(hangup: &mut Option<Signal>) {
+    match hangup {
+        Some(hangup) => {
+            hangup.recv().await;
+        }
+        None => future::pending().await,
+    }
+}
+
+/// Signals when the config file at `path` should be reloaded, i.e. when
+/// the file is modified or the process receives SIGHUP.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    hangup: Option<Signal>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        let hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => Some(hangup),
+            Err(err) => {
+                log::warn!("could not listen for SIGHUP: {}", err);
+                None
+            }
+        };
+        Self {
+            modified: modified_time(&path),
+            path,
+            hangup,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub async fn changed(&mut self) {
+        loop {
+            let received_hangup = tokio::select! {
+                _ = recv_hangup(&mut self.hangup) => true,
+                _ = sleep(POLL_INTERVAL) => false,
+            };
+            let modified = modified_time(&self.path);
+            if received_hangup || modified != self.modified {
+                self.modified = modified;
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use eth2::types::Hash256;
+use reqwest::{Client, Error as HTTPError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Error as JSONError, Value};
+use std::num::ParseIntError;
+use thiserror::Error;
+
+const JSON_RPC_VERSION: &str = "2.0";
+
+#[derive(Error, Debug)]
+pub enum ExecutionClientError {
+    #[error("http error: {0}")]
+    HTTPClient(#[from] HTTPError),
+    #[error("json error: {0}")]
+    SerdeError(#[from] JSONError),
+    #[error("JSON-RPC error {code}: {message}")]
+    RPCError { code: i64, message: String },
+    #[error("JSON-RPC response has neither a result nor an error")]
+    MissingResult,
+    #[error("`eth_syncing` returned `true` rather than the sync progress")]
+    UnexpectedSyncStatus,
+    #[error("could not parse quantity `{0}`: {1}")]
+    QuantityError(String, ParseIntError),
+}
+
+type RPCResult<T> = Result<T, ExecutionClientError>;
+
+#[derive(Serialize)]
+struct JSONRPCRequest<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: &'a [Value],
+}
+
+#[derive(Deserialize)]
+struct JSONRPCError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct JSONRPCResponse<T> {
+    result: Option<T>,
+    error: Option<JSONRPCError>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncingData {
+    highest_block: String,
+}
+
+// `eth_syncing` returns `false` if the node is not syncing
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SyncingResponse {
+    NotSyncing(bool),
+    Syncing(SyncingData),
+}
+
+#[derive(Deserialize)]
+struct BlockData {
+    hash: Hash256,
+    number: String,
+}
+
+/// Progress of an execution node that is syncing.
+#[derive(Debug, Clone, Copy)]
+pub struct SyncProgress {
+    pub highest_block: u64,
+}
+
+fn parse_quantity(quantity: &str) -> RPCResult<u64> {
+    u64::from_str_radix(quantity.trim_start_matches("0x"), 16)
+        .map_err(|err| ExecutionClientError::QuantityError(quantity.to_string(), err))
+}
+
+/// Client for the JSON-RPC API of an execution node.
+#[derive(Clone, Debug)]
+pub struct ExecutionClient {
+    http: Client,
+    endpoint: String,
+}
+
+impl ExecutionClient {
+    pub fn new(client: Client, endpoint: &str) -> Self {
+        Self {
+            http: client,
+            endpoint: endpoint.to_string(),
+        }
+    }
+
+    pub fn get_endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: &[Value]) -> RPCResult<T> {
+        let request = JSONRPCRequest {
+            jsonrpc: JSON_RPC_VERSION,
+            id: 1,
+            method,
+            params,
+        };
+        let response = self.http.post(&self.endpoint).json(&request).send().await?;
+        let body = response.bytes().await?;
+        let response: JSONRPCResponse<T> = serde_json::from_slice(&body)?;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(ExecutionClientError::RPCError {
+                code: error.code,
+                message: error.message,
+            }),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(ExecutionClientError::MissingResult),
+        }
+    }
+
+    pub async fn get_client_version(&self) -> RPCResult<String> {
+        self.call("web3_clientVersion", &[]).await
+    }
+
+    /// Return the sync progress of the node or `None` if it is not syncing.
+    pub async fn get_sync_progress(&self) -> RPCResult<Option<SyncProgress>> {
+        match self.call("eth_syncing", &[]).await? {
+            SyncingResponse::NotSyncing(false) => Ok(None),
+            SyncingResponse::NotSyncing(true) => Err(ExecutionClientError::UnexpectedSyncStatus),
+            SyncingResponse::Syncing(data) => Ok(Some(SyncProgress {
+                highest_block: parse_quantity(&data.highest_block)?,
+            })),
+        }
+    }
+
+    pub async fn get_block_number(&self) -> RPCResult<u64> {
+        let block_number: String = self.call("eth_blockNumber", &[]).await?;
+        parse_quantity(&block_number)
+    }
+
+    pub async fn get_peer_count(&self) -> RPCResult<u64> {
+        let peer_count: String = self.call("net_peerCount", &[]).await?;
+        parse_quantity(&peer_count)
+    }
+
+    /// Return the hash of the block of the node at height `number`.
+    pub async fn get_block_hash(&self, number: u64) -> RPCResult<Hash256> {
+        let params = [json!(format!("{:#x}", number)), json!(false)];
+        let block: BlockData = self.call("eth_getBlockByNumber", &params).await?;
+        Ok(block.hash)
+    }
+
+    /// Return the hash and number of the latest block of the node.
+    pub async fn get_latest_block(&self) -> RPCResult<(Hash256, u64)> {
+        let params = [json!("latest"), json!(false)];
+        let block: BlockData = self.call("eth_getBlockByNumber", &params).await?;
+        Ok((block.hash, parse_quantity(&block.number)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_sync_progress_it_cannot_parse() {
+        let parse = |response: Value| serde_json::from_value::<SyncingResponse>(response);
+        assert!(matches!(
+            parse(json!(false)),
+            Ok(SyncingResponse::NotSyncing(false))
+        ));
+        assert!(matches!(
+            parse(json!({"startingBlock": "0x0", "currentBlock": "0x10", "highestBlock": "0x20"})),
+            Ok(SyncingResponse::Syncing(_))
+        ));
+        assert!(parse(json!({"currentBlock": "0x10"})).is_err());
+    }
+}
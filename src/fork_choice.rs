@@ -1,4 +1,4 @@
-use eth2::types::{Epoch, Hash256, Slot};
+use eth2::types::{Checkpoint, Hash256, Slot};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::TryFrom;
@@ -15,7 +15,7 @@ pub struct ProtoNode {
 
 #[derive(Serialize, Deserialize)]
 pub struct ProtoArray {
-    finalized_epoch: Epoch,
+    finalized_checkpoint: Checkpoint,
     nodes: Vec<ProtoNode>,
     indices: HashMap<Hash256, usize>,
 }
@@ -23,41 +23,26 @@ pub struct ProtoArray {
 #[derive(Clone, Default)]
 pub struct ForkChoice {
     pub tree: Arc<Mutex<ForkChoiceNode>>,
-    slots_per_epoch: u64,
 }
 
 impl ForkChoice {
-    // pub fn new(block: Coordinate, slots_per_epoch: u64) -> Self {
-    //     Self {
-    //         tree: Arc::new(Mutex::new(ForkChoiceNode {
-    //             slot: block.slot,
-    //             root: block.root,
-    //             children: vec![],
-    //             weight: 0,
-    //             is_canonical: false,
-    //         })),
-    //         slots_per_epoch,
-    //     }
-    // }
-
-    // pub fn update(&mut self, proto_array: ProtoArray) {
-    //     let finalized_slot = proto_array.finalized_epoch.start_slot(self.slots_per_epoch);
-    //     let node_count = proto_array.nodes.len();
-    //     match ForkChoiceNode::try_from((proto_array, finalized_slot)) {
-    //         Ok(..) => {
-    //             log::trace!(
-    //                 "updated proto array starting at {} with {} nodes",
-    //                 finalized_slot,
-    //                 node_count,
-    //             );
-    //             // let _ = self.tree.write().map(|mut inner| {
-    //             //     *inner = fork_choice;
-    //             //     Some(())
-    //             // });
-    //         }
-    //         Err(err) => log::warn!("failed to update fork choice: {}", err),
-    //     }
-    // }
+    pub fn update(&self, proto_array: ProtoArray) {
+        let finalized_checkpoint = proto_array.finalized_checkpoint;
+        let node_count = proto_array.nodes.len();
+        match ForkChoiceNode::try_from(proto_array) {
+            Ok(fork_choice) => {
+                log::trace!(
+                    "updated proto array starting at {:?} in epoch {} with {} nodes",
+                    finalized_checkpoint.root,
+                    finalized_checkpoint.epoch,
+                    node_count,
+                );
+                let mut tree = self.tree.lock().expect("can lock tree");
+                *tree = fork_choice;
+            }
+            Err(err) => log::warn!("failed to update fork choice: {}", err),
+        }
+    }
 }
 
 #[derive(Serialize, Default, Debug)]
@@ -71,72 +56,81 @@ pub struct ForkChoiceNode {
 
 #[derive(Debug)]
 pub enum ForkChoiceError {
-    MissingFinalizedNode,
+    MissingFinalizedNode(Hash256),
+    InvalidIndex(usize),
 }
 
 impl std::fmt::Display for ForkChoiceError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::MissingFinalizedNode => write!(
+            Self::MissingFinalizedNode(root) => write!(
                 f,
-                "missing finalized node in provided data (check API response?)"
+                "missing finalized node {:?} in provided data (check API response?)",
+                root
             ),
+            Self::InvalidIndex(index) => {
+                write!(f, "provided data refers to missing node at index {}", index)
+            }
         }
     }
 }
 
-impl TryFrom<(ProtoArray, Slot)> for ForkChoiceNode {
+impl TryFrom<ProtoArray> for ForkChoiceNode {
     type Error = ForkChoiceError;
 
-    fn try_from((proto_array, finalized_slot): (ProtoArray, Slot)) -> Result<Self, Self::Error> {
-        let mut parent_index_to_children: HashMap<usize, Vec<Hash256>> = HashMap::new();
-
-        let first_node = &proto_array.nodes[0];
-        let best_descendant = first_node.best_descendant;
-
-        let mut finalized_root = None;
-        for node in proto_array.nodes.iter() {
-            if node.slot < finalized_slot {
-                continue;
-            }
-            if node.slot == finalized_slot {
-                finalized_root = Some(node.root);
-            }
+    fn try_from(proto_array: ProtoArray) -> Result<Self, Self::Error> {
+        let finalized_root = proto_array.finalized_checkpoint.root;
+        let finalized_index = *proto_array
+            .indices
+            .get(&finalized_root)
+            .ok_or(ForkChoiceError::MissingFinalizedNode(finalized_root))?;
+        let finalized_node = proto_array
+            .nodes
+            .get(finalized_index)
+            .ok_or(ForkChoiceError::InvalidIndex(finalized_index))?;
+        let best_descendant = finalized_node.best_descendant;
 
+        let mut parent_index_to_children: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (index, node) in proto_array.nodes.iter().enumerate() {
             if let Some(parent_index) = node.parent {
+                // parents always precede their children so this also rules out cycles
+                if parent_index >= index {
+                    return Err(ForkChoiceError::InvalidIndex(parent_index));
+                }
                 let children = parent_index_to_children.entry(parent_index).or_default();
-                children.push(node.root);
+                children.push(index);
             }
         }
-        finalized_root
-            .ok_or(ForkChoiceError::MissingFinalizedNode)
-            .map(|root| {
-                build_fork_choice_tree(
-                    &root,
-                    &parent_index_to_children,
-                    &proto_array,
-                    best_descendant,
-                )
-            })
+
+        build_fork_choice_tree(
+            finalized_index,
+            &parent_index_to_children,
+            &proto_array,
+            best_descendant,
+        )
     }
 }
 
 fn build_fork_choice_tree(
-    root: &Hash256,
-    parent_index_to_children: &HashMap<usize, Vec<Hash256>>,
+    index: usize,
+    parent_index_to_children: &HashMap<usize, Vec<usize>>,
     proto_array: &ProtoArray,
     best_descendant: Option<u64>,
-) -> ForkChoiceNode {
-    let index = proto_array.indices[root];
-    let proto_node = &proto_array.nodes[index];
+) -> Result<ForkChoiceNode, ForkChoiceError> {
+    let proto_node = proto_array
+        .nodes
+        .get(index)
+        .ok_or(ForkChoiceError::InvalidIndex(index))?;
     let is_canonical = match (best_descendant, proto_node.best_descendant) {
         (Some(head), Some(current)) => head == current,
+        // the head itself has no best descendant
+        (Some(head), None) => head == index as u64,
         _ => false,
     };
-    let children = if let Some(children) = parent_index_to_children.get(&index) {
-        children
+    let children = match parent_index_to_children.get(&index) {
+        Some(children) => children
             .iter()
-            .map(|child| {
+            .map(|&child| {
                 build_fork_choice_tree(
                     child,
                     parent_index_to_children,
@@ -144,15 +138,107 @@ fn build_fork_choice_tree(
                     best_descendant,
                 )
             })
-            .collect()
-    } else {
-        vec![]
+            .collect::<Result<_, _>>()?,
+        None => vec![],
     };
-    ForkChoiceNode {
+    Ok(ForkChoiceNode {
         slot: proto_node.slot,
         root: proto_node.root,
         weight: proto_node.weight,
         children,
         is_canonical,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn root(byte: u8) -> Hash256 {
+        Hash256::repeat_byte(byte)
+    }
+
+    // `(slot, root, parent, best_descendant)` in the shape served by `/lighthouse/proto_array`
+    fn proto_array(finalized: (u64, u8), nodes: &[(u64, u8, Option<usize>, Option<u64>)]) -> Value {
+        let indices = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, (_, byte, _, _))| (format!("{:?}", root(*byte)), json!(index)))
+            .collect::<serde_json::Map<_, _>>();
+        let nodes = nodes
+            .iter()
+            .map(|(slot, byte, parent, best_descendant)| {
+                json!({
+                    "slot": slot.to_string(),
+                    "root": root(*byte),
+                    "parent": parent,
+                    "weight": 32,
+                    "best_child": null,
+                    "best_descendant": best_descendant,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "prune_threshold": 256,
+            "justified_checkpoint": {"epoch": (finalized.0 + 1).to_string(), "root": root(0xff)},
+            "finalized_checkpoint": {"epoch": finalized.0.to_string(), "root": root(finalized.1)},
+            "nodes": nodes,
+            "indices": indices,
+        })
+    }
+
+    fn decode(value: Value) -> ProtoArray {
+        serde_json::from_value(value).expect("can decode proto array")
+    }
+
+    #[test]
+    fn roots_the_tree_at_the_finalized_block_when_the_epoch_starts_with_a_skipped_slot() {
+        // the finalized checkpoint of epoch 2 points at the block in slot 63 as slot 64 is empty
+        let proto_array = decode(proto_array(
+            (2, 2),
+            &[
+                (60, 1, None, Some(3)),
+                (63, 2, Some(0), Some(3)),
+                (65, 3, Some(1), Some(3)),
+                (66, 4, Some(2), None),
+                (66, 5, Some(1), None),
+            ],
+        ));
+
+        let tree = ForkChoiceNode::try_from(proto_array).expect("can build tree");
+        assert_eq!(tree.slot, Slot::new(63));
+        assert_eq!(tree.root, root(2));
+        assert!(tree.is_canonical);
+        let children = tree
+            .children
+            .iter()
+            .map(|child| (child.root, child.is_canonical))
+            .collect::<Vec<_>>();
+        assert_eq!(children, vec![(root(3), true), (root(5), false)]);
+        let head = &tree.children[0].children[0];
+        assert_eq!((head.root, head.is_canonical), (root(4), true));
+    }
+
+    #[test]
+    fn rejects_malformed_proto_arrays() {
+        let empty = decode(proto_array((2, 2), &[]));
+        assert!(matches!(
+            ForkChoiceNode::try_from(empty),
+            Err(ForkChoiceError::MissingFinalizedNode(_))
+        ));
+
+        let dangling_parent = decode(proto_array((2, 2), &[(63, 2, Some(7), None)]));
+        assert!(matches!(
+            ForkChoiceNode::try_from(dangling_parent),
+            Err(ForkChoiceError::InvalidIndex(7))
+        ));
+
+        let mut value = proto_array((2, 2), &[(63, 2, None, None)]);
+        value["indices"][format!("{:?}", root(2))] = json!(3);
+        assert!(matches!(
+            ForkChoiceNode::try_from(decode(value)),
+            Err(ForkChoiceError::InvalidIndex(3))
+        ));
     }
 }
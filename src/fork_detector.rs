@@ -0,0 +1,276 @@
+use crate::chain::Coordinate;
+use crate::node::{Node, NodeError, Status};
+use eth2::types::{Hash256, Slot};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// bound on the number of blocks walked when checking if one head descends from another
+const MAX_ANCESTRY_DEPTH: usize = 64;
+
+/// A set of nodes whose heads lie on the same chain.
+#[derive(Debug, Serialize, Clone)]
+pub struct ForkBranch {
+    pub head: Coordinate,
+    pub node_ids: Vec<u64>,
+}
+
+pub enum ForkUpdate {
+    Detected(Vec<ForkBranch>),
+    Resolved(ForkBranch),
+}
+
+#[derive(Debug, Default)]
+struct ForkDetectorInner {
+    // block root to (slot, parent root) for any block seen while walking ancestry
+    headers: HashMap<Hash256, (Slot, Hash256)>,
+    // first slot the nodes were observed on more than one branch
+    split_since: Option<Slot>,
+    is_reported: bool,
+}
+
+/// Groups the heads of the monitored nodes into branches and reports
+/// when nodes have been on different branches for longer than some threshold.
+#[derive(Clone, Default)]
+pub struct ForkDetector {
+    inner: Arc<Mutex<ForkDetectorInner>>,
+    threshold_in_slots: u64,
+}
+
+impl ForkDetector {
+    pub fn new(threshold_in_slots: u64) -> Self {
+        Self {
+            inner: Default::default(),
+            threshold_in_slots,
+        }
+    }
+
+    async fn fetch_parent(&self, root: Hash256, node: &Node) -> Result<(Slot, Hash256), NodeError> {
+        {
+            let inner = self.inner.lock().expect("can lock state");
+            if let Some(header) = inner.headers.get(&root) {
+                return Ok(*header);
+            }
+        }
+        let header = node.fetch_block_header(root).await?;
+        let mut inner = self.inner.lock().expect("can lock state");
+        inner.headers.insert(root, header);
+        Ok(header)
+    }
+
+    // returns `None` if the ancestry could not be resolved, e.g. because a block could
+    // not be fetched, as that says nothing about whether the heads are on different branches
+    async fn is_ancestor(
+        &self,
+        ancestor: &Coordinate,
+        descendant: &Coordinate,
+        node: &Node,
+    ) -> Option<bool> {
+        let mut root = descendant.root;
+        for _ in 0..MAX_ANCESTRY_DEPTH {
+            if root == ancestor.root {
+                return Some(true);
+            }
+            match self.fetch_parent(root, node).await {
+                Ok((slot, _)) if slot <= ancestor.slot => return Some(false),
+                Ok((_, parent_root)) => root = parent_root,
+                Err(err) => {
+                    log::warn!(
+                        "could not fetch block {:?} from {}: {}",
+                        root,
+                        node.endpoint,
+                        err
+                    );
+                    node.record_error(&err);
+                    return None;
+                }
+            }
+        }
+        Some(false)
+    }
+
+    async fn group_by_branch(&self, nodes: &[Arc<Node>]) -> Option<Vec<ForkBranch>> {
+        let mut heads = nodes
+            .iter()
+            .filter_map(|node| {
+                let state = node.state.lock().expect("can read state");
+                if !matches!(state.status, Status::Healthy) {
+                    return None;
+                }
+                state.head.map(|head| (node, node.id, head))
+            })
+            .collect::<Vec<_>>();
+        heads.sort_by_key(|(_, _, head)| Reverse(head.slot));
+
+        let mut branches: Vec<(&Arc<Node>, ForkBranch)> = vec![];
+        for (node, id, head) in heads {
+            let mut found_branch = false;
+            for (tip_node, branch) in branches.iter_mut() {
+                if self.is_ancestor(&head, &branch.head, tip_node).await? {
+                    branch.node_ids.push(id);
+                    found_branch = true;
+                    break;
+                }
+            }
+            if !found_branch {
+                let branch = ForkBranch {
+                    head,
+                    node_ids: vec![id],
+                };
+                branches.push((node, branch));
+            }
+        }
+        Some(branches.into_iter().map(|(_, branch)| branch).collect())
+    }
+
+    fn prune(&self, slot: Slot) {
+        let mut inner = self.inner.lock().expect("can lock state");
+        let horizon = slot.saturating_sub(2 * MAX_ANCESTRY_DEPTH as u64);
+        inner
+            .headers
+            .retain(|_, (header_slot, _)| *header_slot >= horizon);
+    }
+
+    /// Recompute the branches of the given nodes at `slot`, returning any change
+    /// in the fork status of the monitored network. The update is skipped if the
+    /// branches could not be resolved.
+    pub async fn update(&self, slot: Slot, nodes: &[Arc<Node>]) -> Option<ForkUpdate> {
+        let branches = self.group_by_branch(nodes).await;
+        self.prune(slot);
+        let mut branches = branches?;
+
+        let mut inner = self.inner.lock().expect("can lock state");
+        match branches.len() {
+            0 => None,
+            1 => {
+                inner.split_since = None;
+                if inner.is_reported {
+                    inner.is_reported = false;
+                    branches.pop().map(ForkUpdate::Resolved)
+                } else {
+                    None
+                }
+            }
+            _ => {
+                let split_since = *inner.split_since.get_or_insert(slot);
+                let is_past_threshold = (slot - split_since).as_u64() > self.threshold_in_slots;
+                if is_past_threshold && !inner.is_reported {
+                    inner.is_reported = true;
+                    Some(ForkUpdate::Detected(branches))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SystemClock;
+    use crate::config::EndpointDescription;
+    use crate::mock_beacon_node::{Failure, MockBeaconNode};
+    use crate::store::Store;
+    use reqwest::Client;
+
+    const THRESHOLD_IN_SLOTS: u64 = 2;
+
+    fn block(slot: u64, byte: u8) -> Coordinate {
+        Coordinate {
+            slot: Slot::new(slot),
+            root: Hash256::repeat_byte(byte),
+        }
+    }
+
+    async fn node_for(mock: &MockBeaconNode) -> Arc<Node> {
+        let description = EndpointDescription {
+            consensus: mock.spawn(),
+            ..Default::default()
+        };
+        let clock = Arc::new(SystemClock);
+        let store = Store::open(None, clock.clone()).expect("can open store");
+        let node = Arc::new(Node::new(&description, Client::new(), store, None, clock));
+        assert_eq!(node.refresh_status().await, Status::Healthy);
+        node
+    }
+
+    // a pair of nodes with heads on the chain `1 <- 2 <- 3`
+    async fn nodes_on_the_same_chain() -> (MockBeaconNode, MockBeaconNode, Vec<Arc<Node>>) {
+        let (a, b) = (MockBeaconNode::new("a", "a"), MockBeaconNode::new("b", "b"));
+        for head in [block(1, 1), block(2, 2), block(3, 3)] {
+            a.push_head(head);
+            b.push_head(head);
+        }
+        let nodes = vec![node_for(&a).await, node_for(&b).await];
+        for node in &nodes {
+            node.update_head(block(3, 3));
+        }
+        (a, b, nodes)
+    }
+
+    #[tokio::test]
+    async fn reports_forks_past_the_threshold_until_resolved() {
+        let (_a, b, nodes) = nodes_on_the_same_chain().await;
+        let detector = ForkDetector::new(THRESHOLD_IN_SLOTS);
+        assert!(detector.update(Slot::new(3), &nodes).await.is_none());
+
+        // `b` moves to a sibling of the shared head
+        b.reorg_to(block(4, 0xb4), Hash256::repeat_byte(2), 1, 32);
+        nodes[1].update_head(block(4, 0xb4));
+        for slot in 4..=4 + THRESHOLD_IN_SLOTS {
+            assert!(detector.update(Slot::new(slot), &nodes).await.is_none());
+        }
+        match detector
+            .update(Slot::new(5 + THRESHOLD_IN_SLOTS), &nodes)
+            .await
+        {
+            Some(ForkUpdate::Detected(branches)) => {
+                let heads = branches
+                    .iter()
+                    .map(|branch| (branch.head.root, branch.node_ids.clone()))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    heads,
+                    vec![
+                        (block(4, 0xb4).root, vec![nodes[1].id]),
+                        (block(3, 3).root, vec![nodes[0].id])
+                    ]
+                );
+            }
+            _ => panic!("expected the fork to be detected"),
+        }
+        // only reported once
+        assert!(detector.update(Slot::new(8), &nodes).await.is_none());
+
+        // `b` builds on the head of `a` again
+        b.reorg_to(block(9, 9), Hash256::repeat_byte(3), 1, 32);
+        nodes[1].update_head(block(9, 9));
+        match detector.update(Slot::new(9), &nodes).await {
+            Some(ForkUpdate::Resolved(branch)) => {
+                assert_eq!(branch.head.root, block(9, 9).root);
+                assert_eq!(branch.node_ids.len(), 2);
+            }
+            _ => panic!("expected the fork to be resolved"),
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_updates_when_ancestry_cannot_be_resolved() {
+        let (a, _b, nodes) = nodes_on_the_same_chain().await;
+        nodes[1].update_head(block(2, 2));
+        let path = format!("/eth/v1/beacon/headers/{:?}", Hash256::repeat_byte(3));
+        a.inject_failure(&path, Failure::Status(500));
+
+        let detector = ForkDetector::new(THRESHOLD_IN_SLOTS);
+        for slot in 3..=3 + 2 * THRESHOLD_IN_SLOTS {
+            assert!(detector.update(Slot::new(slot), &nodes).await.is_none());
+        }
+        assert!(!nodes[0].errors().is_empty());
+
+        a.clear_failures();
+        assert!(detector.update(Slot::new(10), &nodes).await.is_none());
+        assert!(detector.inner.lock().unwrap().split_since.is_none());
+    }
+}
@@ -1,10 +1,28 @@
 mod api_server;
+mod backoff;
 mod beacon_api_client;
+mod beacon_events;
 mod chain;
+mod clock;
 mod config;
+mod config_checker;
+mod config_watcher;
+mod execution_client;
 mod fork_choice;
+mod fork_detector;
+mod metrics;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock_beacon_node;
 mod monitor;
 mod node;
+mod presets;
+mod recording;
+mod reorgs;
+mod replay;
+mod store;
 mod timer;
 
+pub use chain::Coordinate;
+pub use config::Config;
 pub use monitor::Monitor;
+pub use replay::Replay;
@@ -1,6 +1,6 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
-use ethereum_consensus_monitor::Monitor;
+use ethereum_consensus_monitor::{Config, Monitor, Replay};
 use std::fs;
 use std::path::PathBuf;
 
@@ -8,6 +8,19 @@ use std::path::PathBuf;
 struct Options {
     #[clap(long, default_value = "config.toml")]
     config_path: PathBuf,
+    /// Record everything received from the monitored nodes to this file
+    #[clap(long)]
+    record_path: Option<PathBuf>,
+    /// Replay a recording made with `--record-path` instead of connecting to live nodes
+    #[clap(long)]
+    replay_path: Option<PathBuf>,
+    /// Speed of the replay relative to real time
+    #[clap(long, default_value = "1.0")]
+    replay_speed: f64,
+    /// Name of a known network (e.g. `mainnet`) whose preset fills in the network constants,
+    /// overriding `network.name` in the config
+    #[clap(long)]
+    network: Option<String>,
 }
 
 #[tokio::main]
@@ -18,8 +31,26 @@ async fn main() -> Result<()> {
 
     let config = fs::read_to_string(&options.config_path)
         .with_context(|| format!("failed to read config from {:?}", options.config_path))?;
+    let mut config = Config::from_toml(&config)
+        .with_context(|| format!("invalid config at {:?}", options.config_path))?;
+    if let Some(network) = options.network {
+        config.network.name = network;
+    }
 
-    let monitor = Monitor::from_config(&config);
+    let monitor = match (options.record_path, options.replay_path) {
+        (Some(_), Some(_)) => bail!("cannot record and replay at the same time"),
+        (Some(record_path), None) => Monitor::recording_to(config, &record_path)
+            .await?
+            .reloading_from(options.config_path),
+        (None, Some(replay_path)) => {
+            let replay = Replay::load(&replay_path, options.replay_speed)
+                .with_context(|| format!("failed to load recording from {:?}", replay_path))?;
+            Monitor::replaying(config, replay).await?
+        }
+        (None, None) => Monitor::from_config(config)
+            .await?
+            .reloading_from(options.config_path),
+    };
     monitor.run().await;
 
     Ok(())
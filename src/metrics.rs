@@ -0,0 +1,144 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// upper bounds (in seconds) of the buckets used for request latencies
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(buckets: &'static [f64]) -> Self {
+        Self {
+            buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        for (bound, count) in self.buckets.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Metrics collected by a `BeaconAPIClient` about its requests.
+#[derive(Debug)]
+pub struct ClientMetrics {
+    request_latency: Mutex<Histogram>,
+    sse_reconnects: AtomicU64,
+}
+
+impl Default for ClientMetrics {
+    fn default() -> Self {
+        Self {
+            request_latency: Mutex::new(Histogram::new(&LATENCY_BUCKETS)),
+            sse_reconnects: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ClientMetrics {
+    pub fn observe_request_latency(&self, latency: Duration) {
+        let mut histogram = self.request_latency.lock().expect("can lock metrics");
+        histogram.observe(latency.as_secs_f64());
+    }
+
+    pub fn request_latency(&self) -> Histogram {
+        self.request_latency
+            .lock()
+            .expect("can lock metrics")
+            .clone()
+    }
+
+    pub fn inc_sse_reconnects(&self) {
+        self.sse_reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn sse_reconnects(&self) -> u64 {
+        self.sse_reconnects.load(Ordering::Relaxed)
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_labels(labels: &[(&str, &str)], extra: Option<(&str, &str)>) -> String {
+    let labels = labels
+        .iter()
+        .chain(extra.iter())
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect::<Vec<_>>();
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+/// Renders metrics in the Prometheus text exposition format.
+#[derive(Default)]
+pub struct MetricsWriter {
+    buffer: String,
+}
+
+impl MetricsWriter {
+    pub fn describe(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.buffer, "# HELP {} {}", name, help);
+        let _ = writeln!(self.buffer, "# TYPE {} {}", name, kind);
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let _ = writeln!(
+            self.buffer,
+            "{}{} {}",
+            name,
+            format_labels(labels, None),
+            value
+        );
+    }
+
+    pub fn histogram(&mut self, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+        for (bound, count) in histogram.buckets.iter().zip(histogram.counts.iter()) {
+            let bound = bound.to_string();
+            let _ = writeln!(
+                self.buffer,
+                "{}_bucket{} {}",
+                name,
+                format_labels(labels, Some(("le", bound.as_str()))),
+                count
+            );
+        }
+        let _ = writeln!(
+            self.buffer,
+            "{}_bucket{} {}",
+            name,
+            format_labels(labels, Some(("le", "+Inf"))),
+            histogram.count
+        );
+        let labels = format_labels(labels, None);
+        let _ = writeln!(self.buffer, "{}_sum{} {}", name, labels, histogram.sum);
+        let _ = writeln!(self.buffer, "{}_count{} {}", name, labels, histogram.count);
+    }
+
+    pub fn finish(self) -> String {
+        self.buffer
+    }
+}
@@ -0,0 +1,426 @@
+//! An in-process beacon node serving a scriptable subset of the beacon API
+//! so that the monitor can be exercised without a real client.
+use crate::chain::Coordinate;
+use eth2::types::{Checkpoint, Hash256, Slot};
+use futures::stream;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::sleep;
+use warp::filters::path::FullPath;
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::sse::Event;
+use warp::{Filter, Reply};
+
+const MOCK_HOST: [u8; 4] = [127, 0, 0, 1];
+const EVENT_BUFFER_SIZE: usize = 64;
+const EVENTS_PATH: &str = "/eth/v1/events";
+const GENESIS_FORK_VERSION: &str = "0x00000000";
+
+/// A failure to inject into the responses of the mock node.
+#[derive(Debug, Clone)]
+pub enum Failure {
+    // respond with an API error and the given status code
+    Status(u16),
+    // respond successfully but with a body that is not valid json
+    MalformedResponse,
+    // wait for the given duration before responding as usual
+    Delay(Duration),
+}
+
+#[derive(Debug)]
+struct MockState {
+    version: String,
+    peer_id: String,
+    peer_count: u64,
+    is_syncing: bool,
+    is_optimistic: bool,
+    el_offline: bool,
+    sync_distance: u64,
+    head: Coordinate,
+    // block root to (slot, parent root) for every head given to the node
+    blocks: HashMap<Hash256, (Slot, Hash256)>,
+    previous_justified: Checkpoint,
+    current_justified: Checkpoint,
+    finalized: Checkpoint,
+    genesis_time: u64,
+    // served from `config/spec` with each value as a string, as real nodes do
+    spec: BTreeMap<String, String>,
+    // (epoch, version) of each fork after genesis, in order
+    forks: Vec<(u64, String)>,
+    deposit_contract_address: String,
+    deposit_chain_id: u64,
+    failures: HashMap<String, Failure>,
+}
+
+/// MockBeaconNode serves `node/version`, `node/syncing`, `node/identity`, `node/peer_count`,
+/// `beacon/genesis`, `beacon/headers/{root}`, `beacon/states/{state_id}/finality_checkpoints`,
+/// `config/spec`, `config/fork_schedule`, `config/deposit_contract` and the `events` stream
+/// from in-memory state.
+#[derive(Clone)]
+pub struct MockBeaconNode {
+    state: Arc<Mutex<MockState>>,
+    events_tx: broadcast::Sender<(String, String)>,
+}
+
+fn root_as_hex(root: &Hash256) -> String {
+    format!("{:?}", root)
+}
+
+fn checkpoint_as_json(checkpoint: &Checkpoint) -> Value {
+    json!({
+        "epoch": checkpoint.epoch.to_string(),
+        "root": root_as_hex(&checkpoint.root),
+    })
+}
+
+fn error_response(code: u16, message: &str) -> Response {
+    let status = StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let body = json!({
+        "code": code,
+        "message": message,
+    });
+    warp::reply::with_status(warp::reply::json(&body), status).into_response()
+}
+
+fn data_response(data: Value) -> Response {
+    warp::reply::json(&json!({ "data": data })).into_response()
+}
+
+fn default_spec() -> BTreeMap<String, String> {
+    [("SECONDS_PER_SLOT", "12"), ("SLOTS_PER_EPOCH", "32")]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+impl MockBeaconNode {
+    pub fn new(version: &str, peer_id: &str) -> Self {
+        let state = MockState {
+            version: version.to_string(),
+            peer_id: peer_id.to_string(),
+            peer_count: 0,
+            is_syncing: false,
+            is_optimistic: false,
+            el_offline: false,
+            sync_distance: 0,
+            head: Coordinate::default(),
+            blocks: HashMap::new(),
+            previous_justified: Checkpoint::default(),
+            current_justified: Checkpoint::default(),
+            finalized: Checkpoint::default(),
+            genesis_time: 0,
+            spec: default_spec(),
+            forks: vec![],
+            deposit_contract_address: format!("0x{}", "00".repeat(20)),
+            deposit_chain_id: 1,
+            failures: HashMap::new(),
+        };
+        let (events_tx, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            events_tx,
+        }
+    }
+
+    /// Start serving the node on an ephemeral local port, returning its endpoint.
+    pub fn spawn(&self) -> String {
+        let events = {
+            let node = self.clone();
+            warp::get()
+                .and(warp::path!("eth" / "v1" / "events"))
+                .and_then(move || {
+                    let node = node.clone();
+                    async move { Ok::<_, Infallible>(node.serve_events().await) }
+                })
+        };
+        let api = {
+            let node = self.clone();
+            warp::get()
+                .and(warp::path::full())
+                .and_then(move |path: FullPath| {
+                    let node = node.clone();
+                    async move { Ok::<_, Infallible>(node.serve(path.as_str()).await) }
+                })
+        };
+        let (addr, server): (SocketAddr, _) =
+            warp::serve(events.or(api)).bind_ephemeral((MOCK_HOST, 0));
+        tokio::spawn(server);
+        format!("http://{}", addr)
+    }
+
+    pub fn set_syncing(&self, is_syncing: bool, sync_distance: u64) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.is_syncing = is_syncing;
+        state.sync_distance = sync_distance;
+    }
+
+    pub fn set_peer_count(&self, peer_count: u64) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.peer_count = peer_count;
+    }
+
+    pub fn set_optimistic(&self, is_optimistic: bool) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.is_optimistic = is_optimistic;
+    }
+
+    pub fn set_el_offline(&self, el_offline: bool) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.el_offline = el_offline;
+    }
+
+    pub fn set_finality(&self, justified: Checkpoint, finalized: Checkpoint) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.previous_justified = state.current_justified;
+        state.current_justified = justified;
+        state.finalized = finalized;
+    }
+
+    pub fn set_genesis_time(&self, genesis_time: u64) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.genesis_time = genesis_time;
+    }
+
+    /// Set the value of `key` in the spec, e.g. `SECONDS_PER_SLOT`.
+    pub fn set_spec_value(&self, key: &str, value: &str) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.spec.insert(key.to_string(), value.to_string());
+    }
+
+    /// Schedule a fork to `version` (e.g. `0x01000000`) at `epoch`, after any already scheduled.
+    pub fn schedule_fork(&self, epoch: u64, version: &str) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.forks.push((epoch, version.to_string()));
+    }
+
+    pub fn set_deposit_contract(&self, address: &str, chain_id: u64) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.deposit_contract_address = address.to_string();
+        state.deposit_chain_id = chain_id;
+    }
+
+    /// Make the node respond to requests for `path` (e.g. `/eth/v1/node/syncing`) with `failure`.
+    pub fn inject_failure(&self, path: &str, failure: Failure) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.failures.insert(path.to_string(), failure);
+    }
+
+    pub fn clear_failures(&self) {
+        let mut state = self.state.lock().expect("can lock state");
+        state.failures.clear();
+    }
+
+    /// Emit an event with the given type and json data on the event stream.
+    pub fn emit_event(&self, event_type: &str, data: Value) {
+        // ignore errors as they only signal lack of subscribers
+        let _ = self
+            .events_tx
+            .send((event_type.to_string(), data.to_string()));
+    }
+
+    /// Advance the head of the node to `head`, a child of the current head,
+    /// and emit the corresponding `head` and `block` events.
+    pub fn push_head(&self, head: Coordinate) {
+        {
+            let mut state = self.state.lock().expect("can lock state");
+            let parent = state.head.root;
+            state.blocks.insert(head.root, (head.slot, parent));
+            state.head = head;
+        }
+        self.emit_head(head);
+    }
+
+    /// Switch the head of the node to `new_head` with the given `parent`,
+    /// emitting a `chain_reorg` event of `depth` before the `head` event.
+    pub fn reorg_to(
+        &self,
+        new_head: Coordinate,
+        parent: Hash256,
+        depth: u64,
+        slots_per_epoch: u64,
+    ) {
+        let old_head = {
+            let mut state = self.state.lock().expect("can lock state");
+            let old_head = state.head;
+            state.blocks.insert(new_head.root, (new_head.slot, parent));
+            state.head = new_head;
+            old_head
+        };
+        self.emit_event(
+            "chain_reorg",
+            json!({
+                "slot": new_head.slot.to_string(),
+                "depth": depth.to_string(),
+                "old_head_block": root_as_hex(&old_head.root),
+                "new_head_block": root_as_hex(&new_head.root),
+                "old_head_state": root_as_hex(&Hash256::zero()),
+                "new_head_state": root_as_hex(&Hash256::zero()),
+                "epoch": new_head.slot.epoch(slots_per_epoch).to_string(),
+            }),
+        );
+        self.emit_head(new_head);
+    }
+
+    fn emit_head(&self, head: Coordinate) {
+        self.emit_event(
+            "block",
+            json!({
+                "slot": head.slot.to_string(),
+                "block": root_as_hex(&head.root),
+            }),
+        );
+        self.emit_event(
+            "head",
+            json!({
+                "slot": head.slot.to_string(),
+                "block": root_as_hex(&head.root),
+                "state": root_as_hex(&Hash256::zero()),
+                "epoch_transition": false,
+            }),
+        );
+    }
+
+    /// Play a scripted sequence of heads, waiting the given delay before each one.
+    pub async fn play_heads(&self, heads: Vec<(Duration, Coordinate)>) {
+        for (delay, head) in heads {
+            sleep(delay).await;
+            self.push_head(head);
+        }
+    }
+
+    fn failure_for(&self, path: &str) -> Option<Failure> {
+        let state = self.state.lock().expect("can lock state");
+        state.failures.get(path).cloned()
+    }
+
+    async fn serve_events(&self) -> Response {
+        if let Some(response) = self.apply_failure(EVENTS_PATH).await {
+            return response;
+        }
+        let events = stream::unfold(self.events_tx.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok((event_type, data)) => {
+                        let event = Event::default().event(event_type).data(data);
+                        return Some((Ok::<_, Infallible>(event), rx));
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        warp::sse::reply(warp::sse::keep_alive().stream(events)).into_response()
+    }
+
+    async fn apply_failure(&self, path: &str) -> Option<Response> {
+        match self.failure_for(path)? {
+            Failure::Status(code) => Some(error_response(code, "injected failure")),
+            Failure::MalformedResponse => Some("{ not json".into_response()),
+            Failure::Delay(duration) => {
+                sleep(duration).await;
+                None
+            }
+        }
+    }
+
+    async fn serve(&self, path: &str) -> Response {
+        if let Some(response) = self.apply_failure(path).await {
+            return response;
+        }
+
+        let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+        let state = self.state.lock().expect("can lock state");
+        match segments.as_slice() {
+            ["eth", "v1", "node", "version"] => data_response(json!({
+                "version": state.version,
+            })),
+            ["eth", "v1", "node", "syncing"] => data_response(json!({
+                "head_slot": state.head.slot.to_string(),
+                "sync_distance": state.sync_distance.to_string(),
+                "is_syncing": state.is_syncing,
+                "is_optimistic": state.is_optimistic,
+                "el_offline": state.el_offline,
+            })),
+            ["eth", "v1", "node", "identity"] => data_response(json!({
+                "peer_id": state.peer_id,
+                "enr": "",
+                "p2p_addresses": [],
+                "discovery_addresses": [],
+                "metadata": {
+                    "seq_number": "0",
+                    "attnets": "0x0000000000000000",
+                    "syncnets": "0x00",
+                },
+            })),
+            ["eth", "v1", "node", "peer_count"] => data_response(json!({
+                "connected": state.peer_count.to_string(),
+                "connecting": "0",
+                "disconnected": "0",
+                "disconnecting": "0",
+            })),
+            ["eth", "v1", "beacon", "genesis"] => data_response(json!({
+                "genesis_time": state.genesis_time.to_string(),
+                "genesis_validators_root": root_as_hex(&Hash256::zero()),
+                "genesis_fork_version": GENESIS_FORK_VERSION,
+            })),
+            ["eth", "v1", "config", "spec"] => data_response(json!(state.spec)),
+            ["eth", "v1", "config", "fork_schedule"] => {
+                let mut previous_version = GENESIS_FORK_VERSION;
+                let mut schedule = vec![json!({
+                    "previous_version": GENESIS_FORK_VERSION,
+                    "current_version": GENESIS_FORK_VERSION,
+                    "epoch": "0",
+                })];
+                for (epoch, version) in &state.forks {
+                    schedule.push(json!({
+                        "previous_version": previous_version,
+                        "current_version": version,
+                        "epoch": epoch.to_string(),
+                    }));
+                    previous_version = version.as_str();
+                }
+                data_response(Value::Array(schedule))
+            }
+            ["eth", "v1", "config", "deposit_contract"] => data_response(json!({
+                "chain_id": state.deposit_chain_id.to_string(),
+                "address": state.deposit_contract_address,
+            })),
+            ["eth", "v1", "beacon", "states", _, "finality_checkpoints"] => data_response(json!({
+                "previous_justified": checkpoint_as_json(&state.previous_justified),
+                "current_justified": checkpoint_as_json(&state.current_justified),
+                "finalized": checkpoint_as_json(&state.finalized),
+            })),
+            ["eth", "v1", "beacon", "headers", root] => {
+                let block = root
+                    .trim_start_matches("0x")
+                    .parse::<Hash256>()
+                    .ok()
+                    .and_then(|root| state.blocks.get(&root).map(|block| (root, *block)));
+                match block {
+                    Some((root, (slot, parent_root))) => data_response(json!({
+                        "root": root_as_hex(&root),
+                        "canonical": true,
+                        "header": {
+                            "message": {
+                                "slot": slot.to_string(),
+                                "proposer_index": "0",
+                                "parent_root": root_as_hex(&parent_root),
+                                "state_root": root_as_hex(&Hash256::zero()),
+                                "body_root": root_as_hex(&Hash256::zero()),
+                            },
+                            "signature": format!("0xc0{}", "00".repeat(95)),
+                        },
+                    })),
+                    None => error_response(404, "block not found"),
+                }
+            }
+            _ => error_response(404, "not found"),
+        }
+    }
+}
@@ -1,189 +1,891 @@
 use crate::api_server::APIServer;
+use crate::backoff::Backoff;
+use crate::beacon_api_client::APIClientError;
+use crate::beacon_events::{
+    AttestationEvent, BeaconEvent, BlockEvent, ChainReorgEvent, ContributionAndProofEvent,
+    EventTopic, FinalizedCheckpointEvent, VoluntaryExitEvent,
+};
 use crate::chain::{Chain, Coordinate};
-use crate::config::Config;
-use crate::node::{Node, Status};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{redact_url, Config, EndpointDescription, NetworkParameters, NodeLabels, Spec};
+use crate::config_checker::{ConfigChecker, ConfigMismatch, ConfigUpdate};
+use crate::config_watcher::ConfigWatcher;
+use crate::fork_choice::ForkChoice;
+use crate::fork_detector::{ForkBranch, ForkDetector, ForkUpdate};
+use crate::node::{derive_node_id, ExecutionHeadCheck, Node, NodeError, Status};
+use crate::recording::Recorder;
+use crate::reorgs::{Reorg, Reorgs};
+use crate::replay::Replay;
+use crate::store::Store;
 use crate::timer::Timer;
+use anyhow::{Context, Result};
+use eth2::types::{Epoch, Slot};
 use futures::{future, TryStreamExt};
 use reqwest::{Client, ClientBuilder};
 use serde::Serialize;
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::broadcast::{self, Sender};
 use tokio::task::{self, JoinHandle};
 use tokio::time::sleep;
 
 const LOCALHOST: [u8; 4] = [0, 0, 0, 0];
-const TEN_MINUTES_AS_SECONDS: u64 = 600;
-const NODE_CONNECT_ATTEMPTS: usize = 128;
+// a connection that lasts this long resets the backoff between reconnects
+const STABLE_CONNECTION_DURATION: Duration = Duration::from_secs(60);
+const EVENT_BUFFER_SIZE_PER_NODE: usize = 64;
+// attestations arrive in large bursts so leave room in the buffer for each node
+const GOSSIP_EVENT_BUFFER_SIZE_PER_NODE: usize = 1024;
+// bound every request to a node so one that stops responding cannot stall
+// the per-slot and per-epoch work for every other node
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(6);
 
 #[derive(Debug, Serialize, Clone)]
 pub enum MonitorEvent {
     #[serde(rename = "new_head")]
     NewHead {
         id: u64,
+        name: Option<String>,
         head: Coordinate,
         syncing: bool,
+        status: Status,
+        head_slot: Option<Slot>,
+        sync_distance: Option<Slot>,
+    },
+    #[serde(rename = "node_added")]
+    NodeAdded {
+        id: u64,
+        endpoint: String,
+        labels: NodeLabels,
+    },
+    #[serde(rename = "node_removed")]
+    NodeRemoved {
+        id: u64,
+        endpoint: String,
+        labels: NodeLabels,
+    },
+    #[serde(rename = "node_labels_updated")]
+    NodeLabelsUpdated {
+        id: u64,
+        endpoint: String,
+        labels: NodeLabels,
+    },
+    #[serde(rename = "peer_id_changed")]
+    PeerIdChanged {
+        id: u64,
+        previous_peer_id: String,
+        peer_id: String,
+    },
+    #[serde(rename = "execution_consistency_changed")]
+    ExecutionConsistencyChanged { id: u64, check: ExecutionHeadCheck },
+    #[serde(rename = "fork_detected")]
+    ForkDetected {
+        slot: Slot,
+        branches: Vec<ForkBranch>,
+    },
+    #[serde(rename = "fork_resolved")]
+    ForkResolved { slot: Slot, branch: ForkBranch },
+    #[serde(rename = "config_mismatch_detected")]
+    ConfigMismatchDetected { mismatches: Vec<ConfigMismatch> },
+    #[serde(rename = "config_mismatch_resolved")]
+    ConfigMismatchResolved,
+    #[serde(rename = "block")]
+    Block { id: u64, block: BlockEvent },
+    #[serde(rename = "attestation")]
+    Attestation {
+        id: u64,
+        attestation: Box<AttestationEvent>,
+    },
+    #[serde(rename = "voluntary_exit")]
+    VoluntaryExit {
+        id: u64,
+        voluntary_exit: VoluntaryExitEvent,
+    },
+    #[serde(rename = "finalized_checkpoint")]
+    FinalizedCheckpoint {
+        id: u64,
+        checkpoint: FinalizedCheckpointEvent,
+    },
+    #[serde(rename = "chain_reorg")]
+    ChainReorg { id: u64, reorg: ChainReorgEvent },
+    #[serde(rename = "contribution_and_proof")]
+    ContributionAndProof {
+        id: u64,
+        contribution: Box<ContributionAndProofEvent>,
     },
 }
 
+impl MonitorEvent {
+    // head events are not forwarded as-is but turned into `NewHead` with the node status
+    fn from_beacon_event(id: u64, event: BeaconEvent) -> Option<Self> {
+        let event = match event {
+            BeaconEvent::Head(_) => return None,
+            BeaconEvent::Block(block) => Self::Block { id, block },
+            BeaconEvent::Attestation(attestation) => Self::Attestation { id, attestation },
+            BeaconEvent::VoluntaryExit(voluntary_exit) => {
+                Self::VoluntaryExit { id, voluntary_exit }
+            }
+            BeaconEvent::FinalizedCheckpoint(checkpoint) => {
+                Self::FinalizedCheckpoint { id, checkpoint }
+            }
+            BeaconEvent::ChainReorg(reorg) => Self::ChainReorg { id, reorg },
+            BeaconEvent::ContributionAndProof(contribution) => {
+                Self::ContributionAndProof { id, contribution }
+            }
+        };
+        Some(event)
+    }
+}
+
 pub struct Monitor {
-    _timer: Timer,
     state: Arc<State>,
+    // config file to watch for changes to the endpoint list
+    config_path: Option<PathBuf>,
 }
 
 pub struct State {
     pub config: Config,
-    pub nodes: Vec<Arc<Node>>,
+    pub spec: Spec,
+    pub timer: Timer,
+    // source of time for the timer and for any timestamps, so that a replay follows the recording
+    clock: Arc<dyn Clock>,
+    nodes: Mutex<Vec<Arc<Node>>>,
+    node_tasks: Mutex<HashMap<u64, JoinHandle<()>>>,
+    // the nodes given in the most recently loaded config, keyed by their consensus endpoint
+    config_endpoints: Mutex<HashMap<String, EndpointDescription>>,
+    http_client: Client,
+    recorder: Option<Recorder>,
     pub chain: Chain,
+    pub fork_choice: ForkChoice,
+    pub fork_detector: ForkDetector,
+    pub config_checker: ConfigChecker,
+    pub reorgs: Reorgs,
+    pub store: Store,
     pub events_tx: Sender<MonitorEvent>,
+    // high-volume gossip events (e.g. attestations) are kept apart so that
+    // subscribers who fall behind on them do not miss any other updates
+    pub gossip_events_tx: Sender<MonitorEvent>,
+}
+
+impl State {
+    /// The nodes currently under monitoring, including any paused nodes.
+    pub fn nodes(&self) -> Vec<Arc<Node>> {
+        self.nodes.lock().expect("can lock nodes").clone()
+    }
+
+    /// The nodes currently under monitoring, excluding any paused nodes.
+    pub fn active_nodes(&self) -> Vec<Arc<Node>> {
+        self.nodes()
+            .into_iter()
+            .filter(|node| !node.is_paused())
+            .collect()
+    }
+
+    pub fn find_node(&self, endpoint: &str) -> Option<Arc<Node>> {
+        let nodes = self.nodes.lock().expect("can lock nodes");
+        nodes.iter().find(|node| node.endpoint == endpoint).cloned()
+    }
+
+    pub fn find_node_by_id(&self, id: u64) -> Option<Arc<Node>> {
+        let nodes = self.nodes.lock().expect("can lock nodes");
+        nodes.iter().find(|node| node.id == id).cloned()
+    }
 }
 
 fn build_node(
-    endpoint: &str,
-    execution_description: Option<&String>,
+    description: &EndpointDescription,
     http_client: Client,
+    store: Store,
+    recorder: Option<Recorder>,
+    clock: Arc<dyn Clock>,
 ) -> Arc<Node> {
-    Arc::new(Node::new(endpoint, execution_description, http_client))
+    Arc::new(Node::new(description, http_client, store, recorder, clock))
 }
 
-fn build_nodes<'a>(
-    endpoints: impl Iterator<Item = (&'a String, Option<&'a String>)>,
+fn build_nodes(
+    descriptions: &[EndpointDescription],
     http_client: &Client,
+    store: &Store,
+    recorder: Option<&Recorder>,
+    clock: &Arc<dyn Clock>,
 ) -> Vec<Arc<Node>> {
-    endpoints
-        .map(|(endpoint, execution_description)| {
-            build_node(endpoint, execution_description, http_client.clone())
+    descriptions
+        .iter()
+        .map(|description| {
+            build_node(
+                description,
+                http_client.clone(),
+                store.clone(),
+                recorder.cloned(),
+                clock.clone(),
+            )
         })
         .collect()
 }
 
-async fn connect_to_node(node: &Arc<Node>) {
-    for _ in 0..NODE_CONNECT_ATTEMPTS {
-        while let Err(err) = node.connect().await {
-            log::warn!("{}", err);
-            sleep(Duration::from_secs(TEN_MINUTES_AS_SECONDS)).await;
-        }
+fn broadcast_event(channel: &Sender<MonitorEvent>, event: MonitorEvent) {
+    if let Ok(subscriber_count) = channel.send(event) {
+        log::debug!("sent updates to {} connected clients", subscriber_count);
     }
+    // ignore errors as they only signal lack of subscribers
 }
 
-async fn stream_head_updates(node: &Arc<Node>, channel: Sender<MonitorEvent>) {
-    let client = &node.api_client;
-    let consensus_type = {
-        let state = node.state.lock().expect("can read state");
-        let consensus_type = state.node_type.clone();
-
-        if consensus_type.is_none() {
-            return;
+async fn record_reorg(node: &Node, state: &State, event: &ChainReorgEvent) {
+    let old_head_slot = match node.fetch_block_header(event.old_head_block).await {
+        Ok((slot, _)) => Some(slot),
+        Err(err) => {
+            log::warn!(
+                "could not fetch old head of reorg from {}: {}",
+                node.endpoint,
+                err
+            );
+            node.record_error(&err);
+            None
         }
-
-        consensus_type.unwrap()
     };
+    let timestamp = state.clock.now().as_secs();
+    let reorg = Reorg::new(node.id, old_head_slot, event, timestamp);
+    state.reorgs.record(reorg);
+}
 
-    let mut stream = Box::pin(client.stream_head(consensus_type));
-    while let Ok(Some(head)) = stream.try_next().await {
-        match head {
-            Ok(head) => {
+// Stream events from the node until the stream fails or ends, returning why it stopped.
+async fn stream_node_events(node: &Arc<Node>, state: &State) -> NodeError {
+    let client = &node.api_client;
+    let channel = &state.events_tx;
+    let id = node.id;
+
+    let mut topics = state.config.monitor.event_topics.clone();
+    // the monitor follows the head of each node through its head events
+    if !topics.contains(&EventTopic::Head) {
+        topics.push(EventTopic::Head);
+    }
+    let mut stream = Box::pin(client.stream_events(&topics));
+    loop {
+        let event = match stream.try_next().await {
+            Ok(Some(event)) => event,
+            Ok(None) => {
+                let err = APIClientError::EventSourceError("event stream ended".to_string());
+                return err.into();
+            }
+            Err(err) => return err.into(),
+        };
+        match event {
+            Ok(BeaconEvent::Head(head)) => {
+                let head = Coordinate::from(&head);
                 node.update_head(head);
-                let event =
-                    node.state
-                        .lock()
-                        .expect("can read state")
-                        .id
-                        .map(|id| MonitorEvent::NewHead {
-                            id,
-                            head,
-                            syncing: false,
-                        });
-                if event.is_none() {
-                    continue;
-                }
-                let event = event.unwrap();
-                let syncing = match node.fetch_status().await {
-                    Ok(status) => {
-                        matches!(status, Status::Syncing)
-                    }
+                let status = match node.fetch_status().await {
+                    Ok(status) => status,
                     Err(err) => {
                         log::warn!("could not fetch node status: {}", err);
-                        false
+                        node.record_error(&err);
+                        Status::Unreachable
                     }
                 };
-                let event = match event {
-                    MonitorEvent::NewHead { id, head, .. } => {
-                        MonitorEvent::NewHead { id, head, syncing }
+                let (head_slot, sync_distance) = node.sync_data();
+                let event = MonitorEvent::NewHead {
+                    id,
+                    name: node.labels().name,
+                    head,
+                    syncing: matches!(status, Status::Syncing),
+                    status,
+                    head_slot,
+                    sync_distance,
+                };
+                broadcast_event(channel, event);
+            }
+            Ok(event) => {
+                if let BeaconEvent::ChainReorg(ref reorg) = event {
+                    record_reorg(node, state, reorg).await;
+                }
+                let channel = match event {
+                    BeaconEvent::Attestation(_) | BeaconEvent::ContributionAndProof(_) => {
+                        &state.gossip_events_tx
                     }
+                    _ => channel,
                 };
-                if let Ok(subscriber_count) = channel.send(event) {
-                    log::debug!(
-                        "sent head updates to {} connected clients",
-                        subscriber_count
-                    );
+                if let Some(event) = MonitorEvent::from_beacon_event(id, event) {
+                    broadcast_event(channel, event);
                 }
-                // ignore errors as they only signal lack of subscribers
             }
             Err(err) => {
-                log::warn!("error streaming head for node: {}", err);
+                log::warn!("error streaming events for node: {}", err);
+                node.record_error(&NodeError::from(err));
+                continue;
+            }
+        }
+    }
+}
+
+// Connect to the node and stream its events, starting over with backoff
+// whenever the connection fails or the stream stops.
+async fn supervise_node(node: &Arc<Node>, state: &State) {
+    let mut backoff = Backoff::default();
+    loop {
+        let error = match node.connect().await {
+            Ok(()) => {
+                let connected_at = Instant::now();
+                let error = stream_node_events(node, state).await;
+                node.api_client.metrics().inc_sse_reconnects();
+                if connected_at.elapsed() >= STABLE_CONNECTION_DURATION {
+                    backoff.reset();
+                }
+                error
+            }
+            Err(err) => err,
+        };
+        node.record_disconnect(&error);
+        let delay = backoff.next_delay();
+        log::warn!(
+            "lost connection to node at {} ({}), reconnecting in {:?}",
+            node.endpoint,
+            error,
+            delay
+        );
+        sleep(delay).await;
+    }
+}
+
+fn spawn_node_task(state: &Arc<State>, node: Arc<Node>) {
+    let id = node.id;
+    let task_state = state.clone();
+    let task = task::spawn(async move {
+        supervise_node(&node, &task_state).await;
+    });
+    let mut tasks = state.node_tasks.lock().expect("can lock tasks");
+    if let Some(previous_task) = tasks.insert(id, task) {
+        previous_task.abort();
+    }
+}
+
+/// Start monitoring the node described by `description`, returning its id or `None`
+/// if a node at the same endpoint or with the same id is already monitored.
+pub(crate) fn add_node(state: &Arc<State>, description: &EndpointDescription) -> Option<u64> {
+    let node = build_node(
+        description,
+        state.http_client.clone(),
+        state.store.clone(),
+        state.recorder.clone(),
+        state.clock.clone(),
+    );
+    {
+        let mut nodes = state.nodes.lock().expect("can lock nodes");
+        let is_monitored = nodes
+            .iter()
+            .any(|other| other.endpoint == node.endpoint || other.id == node.id);
+        if is_monitored {
+            return None;
+        }
+        nodes.push(node.clone());
+    }
+    spawn_node_task(state, node.clone());
+    log::info!("started monitoring node at {}", description.consensus);
+    let event = MonitorEvent::NodeAdded {
+        id: node.id,
+        endpoint: redact_url(&description.consensus),
+        labels: description.labels.clone(),
+    };
+    broadcast_event(&state.events_tx, event);
+    Some(node.id)
+}
+
+fn stop_node_task(state: &State, id: u64) {
+    let task = state.node_tasks.lock().expect("can lock tasks").remove(&id);
+    if let Some(task) = task {
+        task.abort();
+    }
+}
+
+/// Stop monitoring the node with `id`, returning `false` if there is no such node.
+pub(crate) fn remove_node(state: &State, id: u64) -> bool {
+    let node = {
+        let mut nodes = state.nodes.lock().expect("can lock nodes");
+        match nodes.iter().position(|node| node.id == id) {
+            Some(index) => nodes.remove(index),
+            None => return false,
+        }
+    };
+    stop_node_task(state, id);
+    state.chain.remove_status(id);
+    let update = state.config_checker.remove(id);
+    broadcast_config_update(state, update);
+    log::info!("stopped monitoring node at {}", node.endpoint);
+    let event = MonitorEvent::NodeRemoved {
+        id,
+        endpoint: redact_url(&node.endpoint),
+        labels: node.labels(),
+    };
+    broadcast_event(&state.events_tx, event);
+    true
+}
+
+/// Pause or resume the node with `id`, returning `false` if there is no such node.
+pub(crate) fn set_node_paused(state: &Arc<State>, id: u64, paused: bool) -> bool {
+    let node = match state.find_node_by_id(id) {
+        Some(node) => node,
+        None => return false,
+    };
+    if node.is_paused() == paused {
+        return true;
+    }
+    node.set_paused(paused);
+    if paused {
+        stop_node_task(state, id);
+        log::info!("paused monitoring of node at {}", node.endpoint);
+    } else {
+        log::info!("resumed monitoring of node at {}", node.endpoint);
+        spawn_node_task(state, node);
+    }
+    true
+}
+
+/// Replace the labels of the node with `id`, returning `false` if there is no such node.
+pub(crate) fn set_node_labels(state: &State, id: u64, labels: NodeLabels) -> bool {
+    let node = match state.find_node_by_id(id) {
+        Some(node) => node,
+        None => return false,
+    };
+    node.set_labels(labels.clone());
+    let event = MonitorEvent::NodeLabelsUpdated {
+        id: node.id,
+        endpoint: redact_url(&node.endpoint),
+        labels,
+    };
+    broadcast_event(&state.events_tx, event);
+    true
+}
+
+/// Start or stop monitoring nodes as the endpoints in `config` change from the
+/// previously loaded config, leaving any nodes added through the admin API.
+/// Nodes whose description changed are restarted, or relabelled if only their labels changed.
+fn reload_endpoints(state: &Arc<State>, config: &Config) {
+    let configured = config_endpoints(config);
+    let previous = {
+        let mut config_endpoints = state.config_endpoints.lock().expect("can lock endpoints");
+        std::mem::replace(&mut *config_endpoints, configured.clone())
+    };
+    for endpoint in previous.keys() {
+        if configured.contains_key(endpoint) {
+            continue;
+        }
+        if let Some(node) = state.find_node(endpoint) {
+            remove_node(state, node.id);
+        }
+    }
+    for endpoint in &config.monitor.endpoints {
+        let previous = match previous.get(&endpoint.consensus) {
+            Some(previous) => previous,
+            None => {
+                add_node(state, endpoint);
                 continue;
             }
+        };
+        if previous == endpoint {
+            continue;
+        }
+        // nodes removed through the admin API stay removed
+        let node = match state.find_node(&endpoint.consensus) {
+            Some(node) => node,
+            None => continue,
+        };
+        if previous.id == endpoint.id && previous.execution == endpoint.execution {
+            set_node_labels(state, node.id, endpoint.labels.clone());
+        } else {
+            log::info!(
+                "restarting node at {} as its description changed",
+                redact_url(&endpoint.consensus)
+            );
+            remove_node(state, node.id);
+            add_node(state, endpoint);
+        }
+    }
+}
+
+// the endpoints given in `config`, keyed by their consensus endpoint
+fn config_endpoints(config: &Config) -> HashMap<String, EndpointDescription> {
+    config
+        .monitor
+        .endpoints
+        .iter()
+        .map(|endpoint| (endpoint.consensus.clone(), endpoint.clone()))
+        .collect()
+}
+
+async fn load_config(path: &Path) -> Result<Config> {
+    let config = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("failed to read config from {:?}", path))?;
+    Config::from_toml(&config).with_context(|| format!("invalid config at {:?}", path))
+}
+
+async fn watch_config(state: Arc<State>, path: PathBuf) {
+    let mut watcher = ConfigWatcher::new(path);
+    loop {
+        watcher.changed().await;
+        match load_config(watcher.path()).await {
+            Ok(config) => reload_endpoints(&state, &config),
+            Err(err) => log::warn!("could not reload config: {:#}", err),
+        }
+    }
+}
+
+fn find_fork_choice_provider(nodes: &[Arc<Node>]) -> Option<&Arc<Node>> {
+    nodes.iter().find(|node| node.supports_fork_choice())
+}
+
+async fn refresh_statuses(nodes: &[Arc<Node>]) {
+    let refreshes = nodes.iter().map(|node| async move {
+        node.refresh_status().await;
+        node.refresh_execution_status().await;
+        if let Err(err) = node.fetch_peer_count().await {
+            log::warn!("could not fetch peer count from {}: {}", node.endpoint, err);
+            node.record_error(&err);
+        }
+    });
+    future::join_all(refreshes).await;
+}
+
+async fn update_fork_choice(nodes: &[Arc<Node>], fork_choice: &ForkChoice) {
+    let provider = match find_fork_choice_provider(nodes) {
+        Some(provider) => provider,
+        None => {
+            log::trace!("no fork choice provider available");
+            return;
+        }
+    };
+
+    match provider.fetch_fork_choice().await {
+        Ok(proto_array) => {
+            let fork_choice = fork_choice.clone();
+            let result = task::spawn_blocking(move || {
+                fork_choice.update(proto_array);
+            })
+            .await;
+            if let Err(err) = result {
+                log::warn!("could not update fork choice: {}", err);
+            }
+        }
+        Err(err) => {
+            log::warn!(
+                "could not fetch fork choice from {}: {}",
+                provider.endpoint,
+                err
+            );
+            provider.record_error(&err);
+        }
+    }
+}
+
+async fn detect_forks(state: &State, slot: Slot) {
+    let nodes = state.active_nodes();
+    let event = match state.fork_detector.update(slot, &nodes).await {
+        Some(ForkUpdate::Detected(branches)) => {
+            log::warn!(
+                "nodes are on {} different branches at slot {}",
+                branches.len(),
+                slot
+            );
+            MonitorEvent::ForkDetected { slot, branches }
+        }
+        Some(ForkUpdate::Resolved(branch)) => {
+            log::info!("nodes converged on head {} at slot {}", branch.head, slot);
+            MonitorEvent::ForkResolved { slot, branch }
+        }
+        None => return,
+    };
+    broadcast_event(&state.events_tx, event);
+}
+
+async fn check_execution_heads(nodes: &[Arc<Node>], channel: &Sender<MonitorEvent>) {
+    let checks = nodes.iter().map(|node| async move {
+        match node.check_execution_head().await {
+            Ok(Some(check)) => {
+                log::info!(
+                    "execution node of node at {} is now {} with its head",
+                    node.endpoint,
+                    check.consistency
+                );
+                let event = MonitorEvent::ExecutionConsistencyChanged { id: node.id, check };
+                broadcast_event(channel, event);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                log::warn!(
+                    "could not check execution head of node at {}: {}",
+                    node.endpoint,
+                    err
+                );
+                node.record_error(&err);
+            }
+        }
+    });
+    future::join_all(checks).await;
+}
+
+async fn on_slot(state: &State, slot: Slot) {
+    log::trace!("running per-slot work for slot {}", slot);
+    let nodes = state.active_nodes();
+    refresh_statuses(&nodes).await;
+    check_execution_heads(&nodes, &state.events_tx).await;
+    detect_forks(state, slot).await;
+    update_fork_choice(&nodes, &state.fork_choice).await;
+}
+
+async fn update_finality_data(nodes: &[Arc<Node>], chain: &Chain) {
+    let fetches = nodes.iter().map(|node| async move {
+        match node.fetch_finality_data().await {
+            Ok(finality_data) => chain.set_status(node.id, finality_data),
+            Err(err) => {
+                log::warn!(
+                    "could not fetch finality data from {}: {}",
+                    node.endpoint,
+                    err
+                );
+                node.record_error(&err);
+            }
+        }
+    });
+    future::join_all(fetches).await;
+}
+
+async fn check_network_configs(state: &State) {
+    let nodes = state.active_nodes();
+    let fetches = nodes.iter().map(|node| async move {
+        match node.fetch_network_config().await {
+            Ok(config) => Some((node.id, config)),
+            Err(err) => {
+                log::warn!(
+                    "could not fetch network config from {}: {}",
+                    node.endpoint,
+                    err
+                );
+                node.record_error(&err);
+                None
+            }
+        }
+    });
+    let configs = future::join_all(fetches)
+        .await
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let update = state.config_checker.update(configs);
+    broadcast_config_update(state, update);
+}
+
+fn broadcast_config_update(state: &State, update: Option<ConfigUpdate>) {
+    let event = match update {
+        Some(ConfigUpdate::Detected(mismatches)) => {
+            log::warn!(
+                "nodes disagree on {} fields of the network config",
+                mismatches.len()
+            );
+            MonitorEvent::ConfigMismatchDetected { mismatches }
+        }
+        Some(ConfigUpdate::Resolved) => {
+            log::info!("nodes agree on the network config");
+            MonitorEvent::ConfigMismatchResolved
+        }
+        None => return,
+    };
+    broadcast_event(&state.events_tx, event);
+}
+
+async fn refresh_identities(nodes: &[Arc<Node>], channel: &Sender<MonitorEvent>) {
+    let fetches = nodes.iter().map(|node| async move {
+        match node.fetch_identity().await {
+            Ok(Some(previous_peer_id)) => {
+                let peer_id = node.peer_id().unwrap_or_default();
+                log::info!(
+                    "peer id of node at {} changed from {} to {}",
+                    node.endpoint,
+                    previous_peer_id,
+                    peer_id
+                );
+                let event = MonitorEvent::PeerIdChanged {
+                    id: node.id,
+                    previous_peer_id,
+                    peer_id,
+                };
+                broadcast_event(channel, event);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                log::warn!("could not fetch identity from {}: {}", node.endpoint, err);
+                node.record_error(&err);
+            }
+        }
+    });
+    future::join_all(fetches).await;
+}
+
+async fn on_epoch(state: &State, epoch: Epoch) {
+    log::trace!("running per-epoch work for epoch {}", epoch);
+    let nodes = state.active_nodes();
+    update_finality_data(&nodes, &state.chain).await;
+    refresh_identities(&nodes, &state.events_tx).await;
+    check_network_configs(state).await;
+}
+
+/// Drive the periodic work of the monitor from the slot clock so that
+/// node data stays fresh even if a node stops emitting events.
+async fn run_scheduler(state: Arc<State>) {
+    let timer = &state.timer;
+    if timer.is_before_genesis() {
+        log::warn!("before genesis, blocking monitor until then...");
+        timer.wait_for_genesis().await;
+    }
+
+    let mut current_epoch = None;
+    loop {
+        let (slot, epoch) = timer.tick_slot().await;
+        log::trace!("epoch: {}, slot: {}", epoch, slot);
+
+        if current_epoch != Some(epoch) {
+            current_epoch = Some(epoch);
+            on_epoch(&state, epoch).await;
         }
+        on_slot(&state, slot).await;
     }
 }
 
-// async fn find_fork_choice_provider(nodes: &[Arc<Node>]) -> Option<&Arc<Node>> {
-//     nodes.iter().find(|node| node.supports_fork_choice())
-// }
+async fn fetch_network_parameters(nodes: &[Arc<Node>]) -> Option<NetworkParameters> {
+    for node in nodes {
+        match node.fetch_network_parameters().await {
+            Ok(parameters) => return Some(parameters),
+            Err(err) => {
+                log::warn!(
+                    "could not fetch network parameters from {}: {}",
+                    node.endpoint,
+                    err
+                );
+                node.record_error(&err);
+            }
+        }
+    }
+    None
+}
 
 impl Monitor {
-    pub fn from_config(config: &str) -> Self {
-        let config: Config = toml::from_str(config).expect("config is well-formatted TOML");
-        let timer_config = &config.consensus_chain;
-        let timer = Timer::new(
-            timer_config.genesis_time,
-            timer_config.seconds_per_slot,
-            timer_config.slots_per_epoch,
-        );
+    pub async fn from_config(config: Config) -> Result<Self> {
+        Self::new(config, Arc::new(SystemClock), None).await
+    }
 
+    /// Build a monitor that records everything it receives from its nodes to `recording_path`.
+    pub async fn recording_to(config: Config, recording_path: &Path) -> Result<Self> {
+        let recorder = Recorder::create(recording_path)
+            .with_context(|| format!("failed to create recording at {:?}", recording_path))?;
+        Self::new(config, Arc::new(SystemClock), Some(recorder)).await
+    }
+
+    /// Build a monitor that runs against the nodes in the recording served by `replay`.
+    pub async fn replaying(mut config: Config, replay: Replay) -> Result<Self> {
+        // keep the replayed history out of the history of the live nodes
+        config.monitor.data_dir = None;
+        let clock = replay.clock();
+        let replay_endpoints = replay.serve();
+        config.monitor.endpoints = replay_endpoints
+            .into_iter()
+            .map(|(recorded_endpoint, replay_endpoint)| {
+                let description = config
+                    .monitor
+                    .endpoints
+                    .iter()
+                    .find(|endpoint| endpoint.consensus == recorded_endpoint)
+                    .cloned()
+                    .unwrap_or_default();
+                // keep the id of the recorded node as it would otherwise derive from the replay endpoint
+                // the execution nodes are not recorded so are left out of the replay
+                EndpointDescription {
+                    execution: None,
+                    id: description
+                        .id
+                        .or_else(|| Some(derive_node_id(&recorded_endpoint))),
+                    consensus: replay_endpoint,
+                    ..description
+                }
+            })
+            .collect();
+        Self::new(config, clock, None).await
+    }
+
+    async fn new(
+        mut config: Config,
+        clock: Arc<dyn Clock>,
+        recorder: Option<Recorder>,
+    ) -> Result<Self> {
         let http_client = ClientBuilder::new()
-            // .connect_timeout(Duration::from_millis(1000))
-            // .timeout(Duration::from_millis(1000))
+            .connect_timeout(HTTP_CONNECT_TIMEOUT)
+            .timeout(HTTP_REQUEST_TIMEOUT)
             .build()
             .expect("no errors with http client setup");
+        let store = Store::open(config.monitor.data_dir.as_deref(), clock.clone())
+            .context("failed to open history store")?;
+        let config_endpoints = config_endpoints(&config);
         let nodes = build_nodes(
-            config
-                .monitor
-                .endpoints
-                .iter()
-                .map(|endpoint| (&endpoint.consensus, endpoint.execution.as_ref())),
+            &config.monitor.endpoints,
             &http_client,
+            &store,
+            recorder.as_ref(),
+            &clock,
         );
+
+        config.apply_network_preset();
+        let parameters = fetch_network_parameters(&nodes).await;
+        let spec = Spec::resolve(&config, parameters)?;
+        let timer = Timer::new(
+            clock.clone(),
+            Duration::from_secs(spec.genesis_time),
+            Duration::from_secs(spec.seconds_per_slot),
+            spec.slots_per_epoch,
+        );
+
         let node_count = nodes.len();
-        let head_event_buffer_size = 4 * node_count;
-        let (events_tx, _) = broadcast::channel(head_event_buffer_size);
+        let event_buffer_size = EVENT_BUFFER_SIZE_PER_NODE * node_count.max(1);
+        let (events_tx, _) = broadcast::channel(event_buffer_size);
+        let gossip_event_buffer_size = GOSSIP_EVENT_BUFFER_SIZE_PER_NODE * node_count.max(1);
+        let (gossip_events_tx, _) = broadcast::channel(gossip_event_buffer_size);
+        let fork_detector = ForkDetector::new(config.monitor.fork_threshold_in_slots);
+        let config_checker = ConfigChecker::new(&spec);
         let state = State {
             config,
-            nodes,
+            spec,
+            timer,
+            clock,
+            nodes: Mutex::new(nodes),
+            node_tasks: Default::default(),
+            config_endpoints: Mutex::new(config_endpoints),
+            http_client,
+            recorder,
             chain: Default::default(),
+            fork_choice: Default::default(),
+            fork_detector,
+            config_checker,
+            reorgs: Default::default(),
+            store,
             events_tx,
+            gossip_events_tx,
         };
-        Self {
-            _timer: timer,
+        Ok(Self {
             state: Arc::new(state),
-        }
+            config_path: None,
+        })
+    }
+
+    /// Watch the config at `config_path`, adding or removing nodes as its endpoints
+    /// change when the file is modified or the process receives SIGHUP.
+    pub fn reloading_from(mut self, config_path: PathBuf) -> Self {
+        self.config_path = Some(config_path);
+        self
     }
 
     pub async fn run(&self) {
-        let mut tasks = self
-            .state
-            .nodes
-            .iter()
-            .map(|node| {
-                let node = node.clone();
-                let channel = self.state.events_tx.clone();
-                task::spawn(async move {
-                    connect_to_node(&node).await;
-                    stream_head_updates(&node, channel).await;
-                })
-            })
-            .collect::<Vec<JoinHandle<_>>>();
+        for node in self.state.nodes() {
+            spawn_node_task(&self.state, node);
+        }
+        let mut tasks = vec![];
 
         let api_server = APIServer::new(self.state.clone());
         let port = self.state.config.monitor.port;
@@ -191,73 +893,141 @@ impl Monitor {
             api_server.run((LOCALHOST, port)).await;
         });
 
+        let state = self.state.clone();
+        let scheduler_task = task::spawn(async move {
+            run_scheduler(state).await;
+        });
+
         tasks.push(server_task);
+        tasks.push(scheduler_task);
+        if let Some(ref config_path) = self.config_path {
+            let state = self.state.clone();
+            let config_path = config_path.clone();
+            tasks.push(task::spawn(async move {
+                watch_config(state, config_path).await;
+            }));
+        }
         future::join_all(tasks).await;
     }
 }
 
-// tmp scratch
-// let fork_choice_provider = find_fork_choice_provider(&nodes).await;
-// let fork_choice_head = if let Some(ref node) = fork_choice_provider {
-//     let node = node.read().await;
-//     node.head.expect("has head")
-// } else {
-//     Coordinate::default()
-// };
-// let fork_choice = ForkChoice::new(
-//     fork_choice_head,
-//     self.config.consensus_chain.slots_per_epoch,
-// );
-// let fork_choice_handle = fork_choice.clone();
-// let chain = Chain::default();
-// let chain_handle = chain.clone();
-
-// let timer_task = task::spawn(async move {
-//     if timer.is_before_genesis() {
-//         log::warn!("before genesis, blocking monitor until then...");
-//     }
-//     loop {
-//         let (slot, epoch) = timer.tick_slot().await;
-//         log::trace!("epoch: {}, slot: {}", epoch, slot);
-
-//         let fetches = nodes.iter().map(|node| async move {
-//             let mut node = node.write().await;
-//             let result = match node.status {
-//                 Status::Healthy => node.fetch_head_with_hint(slot).await.map(|_| ()),
-//                 Status::Syncing => node.refresh_status().await,
-//                 Status::Unreachable => node.refresh_status().await,
-//             };
-//             if let Err(e) = result {
-//                 log::warn!("{}", e);
-//                 node.status = Status::Unreachable;
-//             }
-//         });
-//         future::join_all(fetches).await;
-
-//         if let Some(ref fork_choice_provider) = fork_choice_provider {
-//             let mut node = fork_choice_provider.write().await;
-//             let result = node.fetch_fork_choice().await;
-//             let mut fork_choice = fork_choice_handle.clone();
-//             match result {
-//                 Ok(proto_array) => {
-//                     let _ = task::spawn_blocking(move || {
-//                         fork_choice.update(proto_array);
-//                     });
-//                 }
-//                 Err(e) => {
-//                     log::warn!("{}", e);
-//                     node.status = Status::Unreachable;
-//                 }
-//             }
-
-//             let result = node.fetch_finality_data(slot).await;
-//             match result {
-//                 Ok(finality_data) => chain_handle.set_status(finality_data),
-//                 Err(e) => {
-//                     log::warn!("{}", e);
-//                     node.status = Status::Unreachable;
-//                 }
-//             }
-//         }
-//     }
-// });
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MonitorConfig;
+    use crate::mock_beacon_node::MockBeaconNode;
+    use eth2::types::Hash256;
+    use serde_json::Value;
+    use tokio::time::timeout;
+    use warp::http::StatusCode;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(10);
+    const HEAD_INTERVAL: Duration = Duration::from_millis(100);
+
+    fn config_for(endpoint: &str) -> Config {
+        Config {
+            monitor: MonitorConfig {
+                endpoints: vec![EndpointDescription {
+                    consensus: endpoint.to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_changed_endpoint_descriptions_on_reload() {
+        let mock = MockBeaconNode::new("Lighthouse/v2.0.0", "mock-peer-id");
+        let endpoint = mock.spawn();
+        let mut config = config_for(&endpoint);
+        let monitor = Monitor::from_config(config.clone())
+            .await
+            .expect("can build monitor");
+        let state = monitor.state.clone();
+        let id = state.nodes()[0].id;
+
+        config.monitor.endpoints[0].labels.name = Some("beacon-1".to_string());
+        reload_endpoints(&state, &config);
+        let nodes = state.nodes();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].id, id);
+        assert_eq!(nodes[0].labels().name.as_deref(), Some("beacon-1"));
+
+        config.monitor.endpoints[0].id = Some(7);
+        reload_endpoints(&state, &config);
+        let nodes = state.nodes();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].id, 7);
+        assert_eq!(nodes[0].labels().name.as_deref(), Some("beacon-1"));
+    }
+
+    #[tokio::test]
+    async fn serves_nodes_and_streams_heads_from_a_mock_node() {
+        let mock = MockBeaconNode::new("Lighthouse/v2.0.0", "mock-peer-id");
+        let endpoint = mock.spawn();
+        let monitor = Monitor::from_config(config_for(&endpoint))
+            .await
+            .expect("can build monitor");
+        let state = monitor.state.clone();
+        assert_eq!(state.spec.seconds_per_slot, 12);
+        assert_eq!(state.spec.slots_per_epoch, 32);
+
+        for node in state.nodes() {
+            spawn_node_task(&state, node);
+        }
+        let routes = APIServer::new(state.clone()).routes();
+        let mut client = warp::test::ws()
+            .path("/api/v1/connect")
+            .handshake(routes.clone())
+            .await
+            .expect("can connect to the websocket");
+
+        // the monitor may not have subscribed to the events of the node yet
+        // so keep advancing its head until the monitor reports one
+        let new_head = timeout(TEST_TIMEOUT, async {
+            let mut slot = 0;
+            loop {
+                slot += 1;
+                mock.push_head(Coordinate {
+                    slot: Slot::new(slot),
+                    root: Hash256::from_low_u64_be(slot),
+                });
+                tokio::select! {
+                    message = client.recv() => {
+                        let message = message.expect("can receive update");
+                        let text = message.to_str().expect("update is text");
+                        let update: Value = serde_json::from_str(text).expect("update is json");
+                        if let Some(new_head) = update.get("new_head") {
+                            return new_head.clone();
+                        }
+                    }
+                    _ = sleep(HEAD_INTERVAL) => {}
+                }
+            }
+        })
+        .await
+        .expect("receives a new head");
+
+        let node_id = state.nodes()[0].id;
+        assert_eq!(new_head["id"], node_id);
+        assert_eq!(new_head["status"], "healthy");
+
+        let response = warp::test::request()
+            .path("/api/v1/nodes")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let nodes: Value = serde_json::from_slice(response.body()).expect("response is json");
+        let nodes = nodes.as_array().expect("response is a list of nodes");
+        assert_eq!(nodes.len(), 1);
+        let node = &nodes[0];
+        assert_eq!(node["id"], node_id);
+        assert_eq!(node["endpoint"], endpoint.as_str());
+        assert_eq!(node["consensus_client"], "Lighthouse");
+        assert_eq!(node["status"], "healthy");
+        assert_eq!(node["identity"]["peer_id"], "mock-peer-id");
+        assert!(!node["head"].is_null());
+    }
+}
@@ -1,16 +1,45 @@
 use crate::beacon_api_client::{APIClientError, BeaconAPIClient};
-use crate::chain::Coordinate;
+use crate::beacon_events::deserialize_quoted_u64;
+use crate::chain::{Coordinate, FinalityData};
+use crate::clock::Clock;
+use crate::config::{EndpointDescription, NetworkParameters, NodeLabels};
+use crate::config_checker::NodeNetworkConfig;
+use crate::execution_client::{ExecutionClient, ExecutionClientError};
+use crate::fork_choice::ProtoArray;
+use crate::recording::Recorder;
+use crate::store::Store;
+use eth2::types::{Hash256, Slot};
 use reqwest::Client;
-use std::collections::hash_map::DefaultHasher;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt;
-use std::hash::{Hash, Hasher};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
-fn hash_of(some_string: &str) -> u64 {
-    let mut s = DefaultHasher::new();
-    some_string.hash(&mut s);
-    s.finish()
+// number of consecutive checks an execution node may not advance while
+// behind the head of its beacon node before it is considered stuck
+const STUCK_THRESHOLD_IN_CHECKS: u64 = 3;
+// number of recent errors kept for each node
+const ERROR_HISTORY_SIZE: usize = 64;
+// number of recent heads whose arrival time is kept for each node
+const HEAD_ARRIVAL_HISTORY_SIZE: usize = 64;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+// largest integer a JavaScript number holds exactly
+pub const MAX_SAFE_NODE_ID: u64 = (1 << 53) - 1;
+
+// FNV-1a as, unlike `DefaultHasher`, its output is stable across releases
+fn stable_hash(key: &str) -> u64 {
+    key.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Derive the id of the node at `endpoint` when no id is configured for it.
+/// The id fits in 53 bits so that it survives as a JSON number in JavaScript.
+pub fn derive_node_id(endpoint: &str) -> u64 {
+    stable_hash(endpoint) & MAX_SAFE_NODE_ID
 }
 
 fn infer_node_type(version: &str) -> Option<ConsensusType> {
@@ -32,11 +61,37 @@ fn infer_node_type(version: &str) -> Option<ConsensusType> {
     None
 }
 
-#[derive(Debug, Clone)]
+fn infer_execution_type(version: &str) -> Option<ExecutionType> {
+    let version = version.to_lowercase();
+    if version.starts_with("geth") {
+        return Some(ExecutionType::Geth);
+    }
+    if version.starts_with("nethermind") {
+        return Some(ExecutionType::Nethermind);
+    }
+    if version.starts_with("besu") {
+        return Some(ExecutionType::Besu);
+    }
+    if version.starts_with("erigon") {
+        return Some(ExecutionType::Erigon);
+    }
+    None
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Status {
     Unreachable,
+    // the execution node of the beacon node is offline
+    #[serde(rename = "el_offline")]
+    ElOffline,
     Syncing,
+    // the head of the beacon node has not been verified by its execution node
+    Optimistic,
+    // the node is synced and can attest
     Healthy,
+    // the node is not queried until it is resumed through the admin API
+    Paused,
 }
 
 impl Default for Status {
@@ -49,8 +104,11 @@ impl fmt::Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Status::Unreachable => write!(f, "unreachable"),
+            Status::ElOffline => write!(f, "el_offline"),
             Status::Syncing => write!(f, "syncing"),
+            Status::Optimistic => write!(f, "optimistic"),
             Status::Healthy => write!(f, "healthy"),
+            Status::Paused => write!(f, "paused"),
         }
     }
 }
@@ -76,53 +134,231 @@ impl fmt::Display for ConsensusType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ExecutionType {
-    // Geth,
-// Nethermind,
-// Besu,
-// Erigon,
-}
-
-// impl fmt::Display for ExecutionType {
-//     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//         // match self {
-//             // ExecutionType::Geth => write!(f, "Geth"),
-//             // ExecutionType::Nethermind => write!(f, "Nethermind"),
-//             // ExecutionType::Besu => write!(f, "Besu"),
-//             // ExecutionType::Erigon => write!(f, "Erigon"),
-//         // }
-//     }
-// }
+    Geth,
+    Nethermind,
+    Besu,
+    Erigon,
+}
+
+impl fmt::Display for ExecutionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecutionType::Geth => write!(f, "Geth"),
+            ExecutionType::Nethermind => write!(f, "Nethermind"),
+            ExecutionType::Besu => write!(f, "Besu"),
+            ExecutionType::Erigon => write!(f, "Erigon"),
+        }
+    }
+}
+
+/// Status of the execution node attached to a node, as of the last refresh.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExecutionStatus {
+    pub reachable: bool,
+    pub syncing: bool,
+    pub block_number: Option<u64>,
+    // only known while syncing
+    pub highest_block: Option<u64>,
+    pub peer_count: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionConsistency {
+    // the execution node is at the payload of the head of the beacon node
+    Consistent,
+    // the beacon node has not verified the payload of its head with the execution node
+    Optimistic,
+    Behind,
+    // behind and has not advanced for a number of checks
+    Stuck,
+    // past the payload of the head of the beacon node on the same chain, e.g. as that head is stale
+    Ahead,
+    // on a different block than the payload of the head of the beacon node
+    Mismatched,
+}
+
+impl fmt::Display for ExecutionConsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecutionConsistency::Consistent => write!(f, "consistent"),
+            ExecutionConsistency::Optimistic => write!(f, "optimistic"),
+            ExecutionConsistency::Behind => write!(f, "behind"),
+            ExecutionConsistency::Stuck => write!(f, "stuck"),
+            ExecutionConsistency::Ahead => write!(f, "ahead"),
+            ExecutionConsistency::Mismatched => write!(f, "mismatched"),
+        }
+    }
+}
+
+/// Result of comparing the head of a beacon node with the latest block of its execution node.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutionHeadCheck {
+    pub consistency: ExecutionConsistency,
+    pub consensus_head: Coordinate,
+    pub payload_block_hash: Hash256,
+    pub payload_block_number: u64,
+    pub execution_block_hash: Hash256,
+    pub execution_block_number: u64,
+    // consecutive checks the execution node has been behind without advancing
+    #[serde(skip)]
+    stalled_checks: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeMetadata {
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    pub seq_number: u64,
+    pub attnets: String,
+    pub syncnets: Option<String>,
+}
+
+/// The identity of a node on the p2p network as reported by `node/identity`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeIdentity {
+    pub peer_id: String,
+    pub enr: String,
+    pub p2p_addresses: Vec<String>,
+    pub discovery_addresses: Vec<String>,
+    pub metadata: NodeMetadata,
+}
+
+/// The number of peers of a node as reported by `node/peer_count`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PeerCount {
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    pub connected: u64,
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    pub connecting: u64,
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    pub disconnected: u64,
+    #[serde(deserialize_with = "deserialize_quoted_u64")]
+    pub disconnecting: u64,
+}
 
 #[derive(Error, Debug)]
 #[error("{0}")]
 pub enum NodeError {
     APIError(#[from] APIClientError),
+    ExecutionError(#[from] ExecutionClientError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorCategory {
+    // the node could not be reached
+    Http,
+    // the node sent a response that could not be decoded
+    Json,
+    // the event stream of the node failed
+    Sse,
+    // the node reported an error
+    Api,
+}
+
+impl From<&NodeError> for ErrorCategory {
+    fn from(err: &NodeError) -> Self {
+        match err {
+            NodeError::APIError(err) => match err {
+                APIClientError::HTTPClient(_) => Self::Http,
+                APIClientError::SerdeError(_)
+                | APIClientError::StringError(_)
+                | APIClientError::Base64Error(_) => Self::Json,
+                APIClientError::EventSourceError(_) | APIClientError::EventError(_) => Self::Sse,
+                APIClientError::APIError(_) => Self::Api,
+            },
+            NodeError::ExecutionError(err) => match err {
+                ExecutionClientError::HTTPClient(_) => Self::Http,
+                ExecutionClientError::SerdeError(_) | ExecutionClientError::QuantityError(..) => {
+                    Self::Json
+                }
+                ExecutionClientError::RPCError { .. }
+                | ExecutionClientError::MissingResult
+                | ExecutionClientError::UnexpectedSyncStatus => Self::Api,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorRecord {
+    pub category: ErrorCategory,
+    pub message: String,
+    pub timestamp: u64,
+}
+
+/// Summary of the errors of a node, counting the recent errors of each category.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ErrorSummary {
+    pub total: u64,
+    pub recent: BTreeMap<ErrorCategory, usize>,
+    pub last: Option<ErrorRecord>,
 }
 
 #[derive(Default, Debug)]
 pub struct NodeState {
-    pub id: Option<u64>,
-
     pub status: Status,
     pub node_type: Option<ConsensusType>,
-    // NOTE: temp for interop
-    pub execution_description: Option<String>,
     pub version: Option<String>,
     // Indicate an attached execution client
     pub execution_node_type: Option<ExecutionType>,
+    pub execution_version: Option<String>,
+    pub execution_status: Option<ExecutionStatus>,
+    pub execution_head: Option<ExecutionHeadCheck>,
 
     // last known head for this node
     pub head: Option<Coordinate>,
+    // as of the last status refresh
+    pub head_slot: Option<Slot>,
+    pub sync_distance: Option<Slot>,
+
+    // when each recent head was received, in milliseconds since the unix epoch
+    pub head_arrivals: VecDeque<(Hash256, u64)>,
+
+    // may change over the lifetime of the node, e.g. if it regenerates its key
+    pub identity: Option<NodeIdentity>,
+    pub peer_count: Option<PeerCount>,
+
+    pub labels: NodeLabels,
+    // paused nodes are kept but not queried
+    pub paused: bool,
+
+    // times the monitor has had to reconnect to the node and why it last did
+    pub reconnect_attempts: u64,
+    pub last_error: Option<String>,
+
+    // most recent errors from querying the node, oldest first
+    pub errors: VecDeque<ErrorRecord>,
+    pub error_count: u64,
+}
+
+impl NodeState {
+    pub fn error_summary(&self) -> ErrorSummary {
+        let mut recent = BTreeMap::new();
+        for error in &self.errors {
+            *recent.entry(error.category).or_default() += 1;
+        }
+        ErrorSummary {
+            total: self.error_count,
+            recent,
+            last: self.errors.back().cloned(),
+        }
+    }
 }
 
 /// Node represents an Ethereum node
 #[derive(Debug)]
 pub struct Node {
+    // configured or derived from the endpoint so it is stable across restarts
+    pub id: u64,
     pub endpoint: String,
     pub api_client: BeaconAPIClient,
+    pub execution_client: Option<ExecutionClient>,
     pub state: Mutex<NodeState>,
+    store: Store,
+    clock: Arc<dyn Clock>,
 }
 
 impl fmt::Display for NodeState {
@@ -134,7 +370,7 @@ impl fmt::Display for NodeState {
             write!(f, "unknown")?
         }
         if let Some(ref node_type) = self.execution_node_type {
-            write!(f, "and execution client {:?}", node_type)?
+            write!(f, " and execution client {}", node_type)?
         }
         write!(f, " with head ")?;
         if let Some(ref head) = self.head {
@@ -155,55 +391,253 @@ impl fmt::Display for Node {
 
 impl Node {
     pub fn new(
-        endpoint: &str,
-        execution_description: Option<&String>,
+        description: &EndpointDescription,
         http_client: Client,
+        store: Store,
+        recorder: Option<Recorder>,
+        clock: Arc<dyn Clock>,
     ) -> Self {
+        let endpoint = &description.consensus;
         let state = NodeState {
-            execution_description: execution_description.map(|s| s.to_string()),
+            labels: description.labels.clone(),
             ..Default::default()
         };
         Self {
+            id: description.id.unwrap_or_else(|| derive_node_id(endpoint)),
             endpoint: endpoint.to_string(),
-            api_client: BeaconAPIClient::new(http_client, endpoint),
+            execution_client: description
+                .execution
+                .as_ref()
+                .map(|endpoint| ExecutionClient::new(http_client.clone(), endpoint)),
+            api_client: BeaconAPIClient::new(http_client, endpoint, recorder),
             state: Mutex::new(state),
+            store,
+            clock,
         }
     }
 
-    // pub fn supports_fork_choice(&self) -> bool {
-    //     let state = self.state.lock().expect("can read state");
-    //     matches!(state.node_type, Some(ConsensusType::Lighthouse))
-    // }
+    pub fn supports_fork_choice(&self) -> bool {
+        let state = self.state.lock().expect("can read state");
+        matches!(state.node_type, Some(ConsensusType::Lighthouse))
+    }
 
-    // pub async fn fetch_fork_choice(&self) -> Result<ProtoArray, NodeError> {
-    //     self.api_client
-    //         .get_lighthouse_fork_choice()
-    //         .await
-    //         .map_err(|e| e.into())
-    // }
+    pub async fn fetch_fork_choice(&self) -> Result<ProtoArray, NodeError> {
+        self.api_client
+            .get_lighthouse_fork_choice()
+            .await
+            .map_err(|e| e.into())
+    }
+
+    pub async fn fetch_finality_data(&self) -> Result<FinalityData, NodeError> {
+        let finality_data: FinalityData = self
+            .api_client
+            .get_finality_checkpoints("head")
+            .await?
+            .into();
+        self.store.record_finality(self.id, finality_data.clone());
+        Ok(finality_data)
+    }
+
+    fn set_status(&self, status: Status) {
+        {
+            let mut inner = self.state.lock().expect("can lock state");
+            // a refresh still in flight when the node was paused must not overwrite its status
+            if inner.paused && status != Status::Paused {
+                return;
+            }
+            inner.status = status.clone();
+        }
+        self.store.record_status(self.id, status);
+    }
 
-    // pub async fn fetch_finality_data(&self, slot: Slot) -> Result<FinalityData, NodeError> {
-    //     // TODO: fix synchronization here...
-    //     self.wait_for_head_delay().await;
-    //     self.api_client
-    //         .get_finality_checkpoints(slot)
-    //         .await
-    //         .map(|checkpoints| checkpoints.into())
-    //         .map_err(|e| e.into())
-    // }
+    pub async fn fetch_network_parameters(&self) -> Result<NetworkParameters, NodeError> {
+        let genesis = self.api_client.get_genesis().await?;
+        let spec = self.api_client.get_spec().await?;
+        let spec_value = |key: &str| -> Result<u64, NodeError> {
+            let value = match spec.get(key) {
+                Some(serde_json::Value::String(value)) => value.parse().ok(),
+                Some(serde_json::Value::Number(value)) => value.as_u64(),
+                _ => None,
+            };
+            value.ok_or_else(|| {
+                APIClientError::APIError(format!("missing or malformed `{}` in spec", key)).into()
+            })
+        };
+        Ok(NetworkParameters {
+            seconds_per_slot: spec_value("SECONDS_PER_SLOT")?,
+            genesis_time: genesis.genesis_time,
+            slots_per_epoch: spec_value("SLOTS_PER_EPOCH")?,
+        })
+    }
+
+    pub async fn fetch_network_config(&self) -> Result<NodeNetworkConfig, NodeError> {
+        Ok(NodeNetworkConfig {
+            spec: self.api_client.get_spec().await?,
+            fork_schedule: self.api_client.get_fork_schedule().await?,
+            deposit_contract: self.api_client.get_deposit_contract().await?,
+        })
+    }
 
     pub async fn fetch_status(&self) -> Result<Status, NodeError> {
         let sync_status = self.api_client.get_sync_status().await?;
-        let mut inner = self.state.lock().expect("can lock state");
-        let status = if sync_status.is_syncing {
+        let status = if sync_status.el_offline {
+            Status::ElOffline
+        } else if sync_status.is_syncing {
             Status::Syncing
+        } else if sync_status.is_optimistic {
+            Status::Optimistic
         } else {
             Status::Healthy
         };
-        inner.status = status.clone();
+        {
+            let mut inner = self.state.lock().expect("can lock state");
+            inner.head_slot = Some(sync_status.head_slot);
+            inner.sync_distance = Some(sync_status.sync_distance);
+        }
+        self.set_status(status.clone());
         Ok(status)
     }
 
+    /// Refresh the status of this node, marking it `Unreachable`
+    /// if the node could not be queried.
+    pub async fn refresh_status(&self) -> Status {
+        match self.fetch_status().await {
+            Ok(status) => status,
+            Err(err) => {
+                log::warn!(
+                    "could not refresh status of node at {}: {}",
+                    self.endpoint,
+                    err
+                );
+                self.record_error(&err);
+                self.set_status(Status::Unreachable);
+                Status::Unreachable
+            }
+        }
+    }
+
+    async fn fetch_execution_status(
+        &self,
+        client: &ExecutionClient,
+    ) -> Result<ExecutionStatus, NodeError> {
+        let needs_version = {
+            let inner = self.state.lock().expect("can lock state");
+            inner.execution_version.is_none()
+        };
+        if needs_version {
+            let version = client.get_client_version().await?;
+            let mut inner = self.state.lock().expect("can lock state");
+            inner.execution_node_type = infer_execution_type(&version);
+            inner.execution_version = Some(version);
+        }
+        let sync_progress = client.get_sync_progress().await?;
+        let block_number = client.get_block_number().await?;
+        let peer_count = client.get_peer_count().await?;
+        Ok(ExecutionStatus {
+            reachable: true,
+            syncing: sync_progress.is_some(),
+            block_number: Some(block_number),
+            highest_block: sync_progress.map(|progress| progress.highest_block),
+            peer_count: Some(peer_count),
+        })
+    }
+
+    /// Refresh the status of the execution node attached to this node, if any,
+    /// marking it unreachable if it could not be queried.
+    pub async fn refresh_execution_status(&self) {
+        let client = match self.execution_client {
+            Some(ref client) => client,
+            None => return,
+        };
+        let status = match self.fetch_execution_status(client).await {
+            Ok(status) => status,
+            Err(err) => {
+                log::warn!(
+                    "could not refresh status of execution node at {}: {}",
+                    client.get_endpoint(),
+                    err
+                );
+                self.record_error(&err);
+                ExecutionStatus::default()
+            }
+        };
+        let mut inner = self.state.lock().expect("can lock state");
+        inner.execution_status = Some(status);
+    }
+
+    /// Compare the execution payload of the head of this node with the latest block of
+    /// its execution node, returning the latest check if the consistency has changed.
+    pub async fn check_execution_head(&self) -> Result<Option<ExecutionHeadCheck>, NodeError> {
+        let client = match self.execution_client {
+            Some(ref client) => client,
+            None => return Ok(None),
+        };
+        let head = match self.state.lock().expect("can lock state").head {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+        let payload = self
+            .api_client
+            .get_execution_payload_summary(head.root)
+            .await?;
+        let (payload_block_hash, payload_block_number) =
+            match (payload.block_hash, payload.block_number) {
+                (Some(block_hash), Some(block_number)) => (block_hash, block_number),
+                // nothing to compare before the merge
+                _ => return Ok(None),
+            };
+        let (execution_block_hash, execution_block_number) = client.get_latest_block().await?;
+        // the head of this node may be stale (e.g. while its event stream is down) so an execution
+        // node past the payload is only on a different chain if its block at that height differs
+        let is_ahead = if !payload.execution_optimistic
+            && execution_block_hash != payload_block_hash
+            && execution_block_number > payload_block_number
+        {
+            client.get_block_hash(payload_block_number).await? == payload_block_hash
+        } else {
+            false
+        };
+
+        let mut inner = self.state.lock().expect("can lock state");
+        let previous = inner.execution_head.take();
+        let is_behind = execution_block_number < payload_block_number;
+        let stalled_checks = match previous {
+            Some(ref previous)
+                if is_behind && previous.execution_block_number == execution_block_number =>
+            {
+                previous.stalled_checks + 1
+            }
+            _ => 0,
+        };
+        let consistency = if payload.execution_optimistic {
+            ExecutionConsistency::Optimistic
+        } else if execution_block_hash == payload_block_hash {
+            ExecutionConsistency::Consistent
+        } else if is_behind && stalled_checks >= STUCK_THRESHOLD_IN_CHECKS {
+            ExecutionConsistency::Stuck
+        } else if is_behind {
+            ExecutionConsistency::Behind
+        } else if is_ahead {
+            ExecutionConsistency::Ahead
+        } else {
+            ExecutionConsistency::Mismatched
+        };
+        let check = ExecutionHeadCheck {
+            consistency,
+            consensus_head: head,
+            payload_block_hash,
+            payload_block_number,
+            execution_block_hash,
+            execution_block_number,
+            stalled_checks,
+        };
+        inner.execution_head = Some(check.clone());
+        let has_changed = previous
+            .map(|previous| previous.consistency != consistency)
+            .unwrap_or(true);
+        Ok(Some(check).filter(|_| has_changed))
+    }
+
     pub async fn fetch_version(&self) -> Result<(), NodeError> {
         let version = self.api_client.get_node_version().await?;
         let mut inner = self.state.lock().expect("can lock state");
@@ -212,11 +646,21 @@ impl Node {
         Ok(())
     }
 
-    pub async fn fetch_identity(&self) -> Result<(), NodeError> {
+    /// Fetch the identity of this node, returning the previous peer id if it has changed.
+    pub async fn fetch_identity(&self) -> Result<Option<String>, NodeError> {
         let identity = self.api_client.get_identity_data().await?;
         let mut inner = self.state.lock().expect("can lock state");
-        let peer_id = identity.peer_id;
-        inner.id = Some(hash_of(&peer_id));
+        let previous = inner.identity.replace(identity);
+        let peer_id = inner.identity.as_ref().map(|identity| &identity.peer_id);
+        Ok(previous
+            .map(|previous| previous.peer_id)
+            .filter(|previous_peer_id| Some(previous_peer_id) != peer_id))
+    }
+
+    pub async fn fetch_peer_count(&self) -> Result<(), NodeError> {
+        let peer_count = self.api_client.get_peer_count().await?;
+        let mut inner = self.state.lock().expect("can lock state");
+        inner.peer_count = Some(peer_count);
         Ok(())
     }
 
@@ -227,8 +671,122 @@ impl Node {
         Ok(())
     }
 
-    pub fn update_head(&self, head: Coordinate) {
+    /// Fetch the slot and parent root of the block with the given `root`.
+    pub async fn fetch_block_header(&self, root: Hash256) -> Result<(Slot, Hash256), NodeError> {
+        let header = self.api_client.get_block_header(root).await?;
+        Ok((header.slot, header.parent_root))
+    }
+
+    /// Add `err` to the recent errors of this node.
+    pub fn record_error(&self, err: &NodeError) {
+        let record = ErrorRecord {
+            category: err.into(),
+            message: err.to_string(),
+            timestamp: self.clock.now().as_secs(),
+        };
+        let mut inner = self.state.lock().expect("can lock state");
+        if inner.errors.len() == ERROR_HISTORY_SIZE {
+            inner.errors.pop_front();
+        }
+        inner.errors.push_back(record);
+        inner.error_count += 1;
+    }
+
+    pub fn errors(&self) -> Vec<ErrorRecord> {
+        let inner = self.state.lock().expect("can lock state");
+        inner.errors.iter().cloned().collect()
+    }
+
+    /// Mark this node as unreachable after losing the connection to it because of `err`.
+    pub fn record_disconnect(&self, err: &NodeError) {
+        self.record_error(err);
+        {
+            let mut inner = self.state.lock().expect("can lock state");
+            inner.reconnect_attempts += 1;
+            inner.last_error = Some(err.to_string());
+        }
+        self.set_status(Status::Unreachable);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        let inner = self.state.lock().expect("can lock state");
+        inner.paused
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        {
+            let mut inner = self.state.lock().expect("can lock state");
+            inner.paused = paused;
+        }
+        // the status of a resumed node is unknown until it is next refreshed
+        let status = if paused {
+            Status::Paused
+        } else {
+            Status::default()
+        };
+        self.set_status(status);
+    }
+
+    pub fn set_labels(&self, labels: NodeLabels) {
         let mut inner = self.state.lock().expect("can lock state");
-        inner.head = Some(head);
+        inner.labels = labels;
+    }
+
+    pub fn labels(&self) -> NodeLabels {
+        let inner = self.state.lock().expect("can lock state");
+        inner.labels.clone()
+    }
+
+    /// Return the head slot and sync distance as of the last status refresh.
+    pub fn sync_data(&self) -> (Option<Slot>, Option<Slot>) {
+        let inner = self.state.lock().expect("can lock state");
+        (inner.head_slot, inner.sync_distance)
+    }
+
+    pub fn peer_id(&self) -> Option<String> {
+        let inner = self.state.lock().expect("can lock state");
+        inner
+            .identity
+            .as_ref()
+            .map(|identity| identity.peer_id.clone())
+    }
+
+    pub fn update_head(&self, head: Coordinate) {
+        {
+            let mut inner = self.state.lock().expect("can lock state");
+            inner.head = Some(head);
+            if inner.head_arrivals.len() == HEAD_ARRIVAL_HISTORY_SIZE {
+                inner.head_arrivals.pop_front();
+            }
+            let arrival = self.clock.now().as_millis() as u64;
+            inner.head_arrivals.push_back((head.root, arrival));
+        }
+        self.store.record_head(self.id, head);
+    }
+
+    pub fn head_arrivals(&self) -> Vec<(Hash256, u64)> {
+        let inner = self.state.lock().expect("can lock state");
+        inner.head_arrivals.iter().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_stable_ids_that_fit_in_a_javascript_number() {
+        let endpoints = ["http://localhost:5052", "http://beacon-1:5052", ""];
+        for endpoint in endpoints {
+            let id = derive_node_id(endpoint);
+            assert!(id <= MAX_SAFE_NODE_ID);
+            assert_eq!(id, derive_node_id(endpoint));
+        }
+        // ids are persisted and referenced by clients, so the derivation must never change
+        assert_eq!(derive_node_id("http://localhost:5052"), 7635214187047612);
+        assert_ne!(
+            derive_node_id("http://beacon-1:5052"),
+            derive_node_id("http://beacon-2:5052")
+        );
     }
 }
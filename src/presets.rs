@@ -0,0 +1,84 @@
+/// Constants of a known public network.
+pub struct NetworkPreset {
+    pub name: &'static str,
+    pub seconds_per_slot: u64,
+    pub slots_per_epoch: u64,
+    pub genesis_time: u64,
+    pub deposit_contract_address: &'static str,
+    pub deposit_chain_id: u64,
+    // lowercase fork name to the epoch it activates at
+    pub fork_epochs: &'static [(&'static str, u64)],
+}
+
+const PRESETS: &[NetworkPreset] = &[
+    NetworkPreset {
+        name: "mainnet",
+        seconds_per_slot: 12,
+        slots_per_epoch: 32,
+        genesis_time: 1606824023,
+        deposit_contract_address: "0x00000000219ab540356cbb839cbe05303d7705fa",
+        deposit_chain_id: 1,
+        fork_epochs: &[
+            ("altair", 74240),
+            ("bellatrix", 144896),
+            ("capella", 194048),
+            ("deneb", 269568),
+            ("electra", 364032),
+            ("fulu", 411392),
+        ],
+    },
+    NetworkPreset {
+        name: "sepolia",
+        seconds_per_slot: 12,
+        slots_per_epoch: 32,
+        genesis_time: 1655733600,
+        deposit_contract_address: "0x7f02c3e3c98b133055b8b348b2ac625669ed295d",
+        deposit_chain_id: 11155111,
+        fork_epochs: &[
+            ("altair", 50),
+            ("bellatrix", 100),
+            ("capella", 56832),
+            ("deneb", 132608),
+            ("electra", 222464),
+            ("fulu", 272640),
+        ],
+    },
+    NetworkPreset {
+        name: "holesky",
+        seconds_per_slot: 12,
+        slots_per_epoch: 32,
+        genesis_time: 1695902400,
+        deposit_contract_address: "0x4242424242424242424242424242424242424242",
+        deposit_chain_id: 17000,
+        fork_epochs: &[
+            ("altair", 0),
+            ("bellatrix", 0),
+            ("capella", 256),
+            ("deneb", 29696),
+            ("electra", 115968),
+            ("fulu", 165120),
+        ],
+    },
+    NetworkPreset {
+        name: "hoodi",
+        seconds_per_slot: 12,
+        slots_per_epoch: 32,
+        genesis_time: 1742213400,
+        deposit_contract_address: "0x00000000219ab540356cbb839cbe05303d7705fa",
+        deposit_chain_id: 560048,
+        fork_epochs: &[
+            ("altair", 0),
+            ("bellatrix", 0),
+            ("capella", 0),
+            ("deneb", 0),
+            ("electra", 2048),
+            ("fulu", 50688),
+        ],
+    },
+];
+
+/// Find the preset for the network with the given `name`, if it is a known network.
+pub fn preset_for(name: &str) -> Option<&'static NetworkPreset> {
+    let name = name.to_lowercase();
+    PRESETS.iter().find(|preset| preset.name == name)
+}
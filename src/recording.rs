@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn now_as_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is after unix epoch")
+        .as_millis() as u64
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedData {
+    // body of a response from the beacon API for the given path (relative to the node's endpoint)
+    Response { path: String, body: String },
+    // an event received on the event stream of the node
+    Event { event_type: String, data: String },
+}
+
+/// A single message received from a node, as stored in a recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedMessage {
+    // unix timestamp in milliseconds when the message was received
+    pub timestamp: u64,
+    pub endpoint: String,
+    #[serde(flatten)]
+    pub data: RecordedData,
+}
+
+// write each message from `rx` to `writer`, flushing whenever there are no more waiting
+fn write_messages(mut writer: BufWriter<File>, rx: mpsc::Receiver<RecordedMessage>) {
+    while let Ok(message) = rx.recv() {
+        for message in std::iter::once(message).chain(rx.try_iter()) {
+            let result = serde_json::to_writer(&mut writer, &message)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(writer));
+            if let Err(err) = result {
+                log::warn!("could not write to recording: {}", err);
+            }
+        }
+        if let Err(err) = writer.flush() {
+            log::warn!("could not write to recording: {}", err);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RecordingWriter {
+    tx: Option<mpsc::Sender<RecordedMessage>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for RecordingWriter {
+    // let the writer drain any pending messages before the recording is closed
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::warn!("recording writer panicked");
+            }
+        }
+    }
+}
+
+/// Recorder writes everything received from the monitored nodes to a file
+/// so that it can be replayed later. Messages are written from a dedicated
+/// thread so that recording does not hold up the event streams of the nodes.
+#[derive(Debug, Clone)]
+pub struct Recorder(Arc<RecordingWriter>);
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let writer = BufWriter::new(File::create(path)?);
+        let (tx, rx) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("recorder".to_string())
+            .spawn(move || write_messages(writer, rx))?;
+        Ok(Self(Arc::new(RecordingWriter {
+            tx: Some(tx),
+            thread: Some(thread),
+        })))
+    }
+
+    fn record(&self, endpoint: &str, data: RecordedData) {
+        let message = RecordedMessage {
+            timestamp: now_as_millis(),
+            endpoint: endpoint.to_string(),
+            data,
+        };
+        let sent = match self.0.tx {
+            Some(ref tx) => tx.send(message).is_ok(),
+            None => false,
+        };
+        if !sent {
+            log::warn!("could not write to recording as its writer has stopped");
+        }
+    }
+
+    pub fn record_response(&self, endpoint: &str, path: &str, body: &[u8]) {
+        let body = String::from_utf8_lossy(body).to_string();
+        let data = RecordedData::Response {
+            path: path.to_string(),
+            body,
+        };
+        self.record(endpoint, data);
+    }
+
+    pub fn record_event(&self, endpoint: &str, event_type: &str, data: &[u8]) {
+        let data = RecordedData::Event {
+            event_type: event_type.to_string(),
+            data: String::from_utf8_lossy(data).to_string(),
+        };
+        self.record(endpoint, data);
+    }
+}
+
+/// Load a recording made by a `Recorder`, in the order the messages were received.
+pub fn load_recording(path: &Path) -> io::Result<Vec<RecordedMessage>> {
+    let file = File::open(path)?;
+    let mut messages = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        messages.push(serde_json::from_str(&line)?);
+    }
+    messages.sort_by_key(|message: &RecordedMessage| message.timestamp);
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn writes_every_message_before_the_recording_is_closed() {
+        let path = std::env::temp_dir().join(format!("recording-test-{}.jsonl", process::id()));
+        let recorder = Recorder::create(&path).expect("can create recording");
+        for slot in 0..100 {
+            let data = format!("{{\"slot\":\"{}\"}}", slot);
+            recorder.record_event("http://localhost:5052", "head", data.as_bytes());
+        }
+        recorder.record_response("http://localhost:5052", "node/version", b"{}");
+        drop(recorder);
+
+        let messages = load_recording(&path).expect("can load recording");
+        assert_eq!(messages.len(), 101);
+        assert!(matches!(
+            messages.last().map(|message| &message.data),
+            Some(RecordedData::Response { .. })
+        ));
+        fs::remove_file(&path).expect("can clean up");
+    }
+}
@@ -0,0 +1,208 @@
+use crate::beacon_events::ChainReorgEvent;
+use crate::chain::Coordinate;
+use eth2::types::{Epoch, Hash256, Slot};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+// number of reorgs to keep in the history of each node
+const MAX_REORGS_PER_NODE: usize = 256;
+
+/// The head a reorg moved away from.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct OldHead {
+    // `None` if the node could not serve the header of the old head
+    pub slot: Option<Slot>,
+    pub root: Hash256,
+}
+
+/// A reorg observed by a single node.
+#[derive(Debug, Clone, Serialize)]
+pub struct Reorg {
+    pub node_id: u64,
+    pub slot: Slot,
+    pub depth: u64,
+    pub old_head: OldHead,
+    pub new_head: Coordinate,
+    pub epoch: Epoch,
+    // unix timestamp in seconds when the reorg was observed
+    pub timestamp: u64,
+}
+
+impl Reorg {
+    pub fn new(
+        node_id: u64,
+        old_head_slot: Option<Slot>,
+        event: &ChainReorgEvent,
+        timestamp: u64,
+    ) -> Self {
+        Self {
+            node_id,
+            slot: event.slot,
+            depth: event.depth,
+            old_head: OldHead {
+                slot: old_head_slot,
+                root: event.old_head_block,
+            },
+            new_head: Coordinate {
+                slot: event.slot,
+                root: event.new_head_block,
+            },
+            epoch: event.epoch,
+            timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReorgObservation {
+    pub node_id: u64,
+    pub timestamp: u64,
+}
+
+/// The same reorg (i.e. from the same old head to the same new head)
+/// as observed across any number of nodes.
+#[derive(Debug, Clone, Serialize)]
+pub struct CorrelatedReorg {
+    pub slot: Slot,
+    pub depth: u64,
+    pub old_head: OldHead,
+    pub new_head: Coordinate,
+    pub epoch: Epoch,
+    pub observations: Vec<ReorgObservation>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReorgFilter {
+    pub node: Option<u64>,
+    // unix timestamps in seconds bounding when the reorg was observed
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+impl ReorgFilter {
+    fn matches(&self, reorg: &Reorg) -> bool {
+        let after_start = self.since.is_none_or(|since| reorg.timestamp >= since);
+        let before_end = self.until.is_none_or(|until| reorg.timestamp <= until);
+        after_start && before_end
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Reorgs(Arc<Mutex<HashMap<u64, VecDeque<Reorg>>>>);
+
+impl Reorgs {
+    pub fn record(&self, reorg: Reorg) {
+        let mut inner = self.0.lock().expect("can lock reorgs");
+        let history = inner.entry(reorg.node_id).or_default();
+        if history.len() == MAX_REORGS_PER_NODE {
+            history.pop_front();
+        }
+        history.push_back(reorg);
+    }
+
+    /// Return the reorgs matching `filter`, merging observations of the same reorg.
+    pub fn query(&self, filter: &ReorgFilter) -> Vec<CorrelatedReorg> {
+        let inner = self.0.lock().expect("can lock reorgs");
+        let mut correlated: Vec<CorrelatedReorg> = vec![];
+        for reorg in inner
+            .values()
+            .flatten()
+            .filter(|reorg| filter.matches(reorg))
+        {
+            let observation = ReorgObservation {
+                node_id: reorg.node_id,
+                timestamp: reorg.timestamp,
+            };
+            let existing = correlated.iter_mut().find(|other| {
+                other.old_head.root == reorg.old_head.root
+                    && other.new_head.root == reorg.new_head.root
+            });
+            match existing {
+                Some(existing) => {
+                    if existing.old_head.slot.is_none() {
+                        existing.old_head.slot = reorg.old_head.slot;
+                    }
+                    existing.observations.push(observation);
+                }
+                None => correlated.push(CorrelatedReorg {
+                    slot: reorg.slot,
+                    depth: reorg.depth,
+                    old_head: reorg.old_head,
+                    new_head: reorg.new_head,
+                    epoch: reorg.epoch,
+                    observations: vec![observation],
+                }),
+            }
+        }
+        if let Some(node_id) = filter.node {
+            correlated.retain(|reorg| {
+                reorg
+                    .observations
+                    .iter()
+                    .any(|observation| observation.node_id == node_id)
+            });
+        }
+        for reorg in correlated.iter_mut() {
+            reorg
+                .observations
+                .sort_by_key(|observation| observation.timestamp);
+        }
+        correlated.sort_by_key(|reorg| reorg.slot);
+        correlated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reorg(node_id: u64, old_head_slot: Option<Slot>, new_head: u64, timestamp: u64) -> Reorg {
+        let event = ChainReorgEvent {
+            slot: Slot::new(new_head),
+            depth: 1,
+            old_head_block: Hash256::from_low_u64_be(new_head * 1000),
+            new_head_block: Hash256::from_low_u64_be(new_head),
+            old_head_state: Hash256::zero(),
+            new_head_state: Hash256::zero(),
+            epoch: Epoch::new(new_head / 32),
+        };
+        Reorg::new(node_id, old_head_slot, &event, timestamp)
+    }
+
+    #[test]
+    fn correlates_reorgs_across_nodes_and_filters_them() {
+        let reorgs = Reorgs::default();
+        reorgs.record(reorg(1, None, 20, 300));
+        reorgs.record(reorg(2, Some(Slot::new(19)), 20, 200));
+        reorgs.record(reorg(2, Some(Slot::new(9)), 10, 100));
+
+        let all = reorgs.query(&ReorgFilter::default());
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].slot, Slot::new(10));
+        assert_eq!(all[1].slot, Slot::new(20));
+        assert_eq!(all[1].old_head.slot, Some(Slot::new(19)));
+        let observers = all[1]
+            .observations
+            .iter()
+            .map(|observation| observation.node_id)
+            .collect::<Vec<_>>();
+        assert_eq!(observers, vec![2, 1]);
+
+        let by_node = reorgs.query(&ReorgFilter {
+            node: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(by_node.len(), 1);
+        assert_eq!(by_node[0].new_head.root, Hash256::from_low_u64_be(20));
+
+        let by_time = reorgs.query(&ReorgFilter {
+            since: Some(150),
+            until: Some(250),
+            ..Default::default()
+        });
+        assert_eq!(by_time.len(), 1);
+        assert_eq!(by_time[0].observations.len(), 1);
+        assert_eq!(by_time[0].observations[0].node_id, 2);
+    }
+}
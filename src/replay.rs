@@ -0,0 +1,187 @@
+use crate::clock::Clock;
+use crate::recording::{load_recording, RecordedData};
+use futures::future::BoxFuture;
+use futures::{stream, StreamExt};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use warp::filters::path::FullPath;
+use warp::http::StatusCode;
+use warp::sse::Event;
+use warp::{Filter, Reply};
+
+const REPLAY_HOST: [u8; 4] = [127, 0, 0, 1];
+
+/// Maps the time of the recording onto the time of the replay.
+#[derive(Debug, Clone, Copy)]
+struct ReplayClock {
+    // time of the first recorded message as a duration since the unix epoch
+    recording_start: Duration,
+    replay_start: Instant,
+    speed: f64,
+}
+
+impl Clock for ReplayClock {
+    fn now(&self) -> Duration {
+        self.recording_start + self.replay_start.elapsed().mul_f64(self.speed)
+    }
+
+    fn sleep_until(&self, deadline: Duration) -> BoxFuture<'static, ()> {
+        let duration = deadline.saturating_sub(self.now()).div_f64(self.speed);
+        Box::pin(sleep(duration))
+    }
+}
+
+#[derive(Default)]
+struct RecordedNode {
+    // path to the (timestamp, body) of each response for that path
+    responses: HashMap<String, Vec<(u64, String)>>,
+    // (timestamp, event type, data) of each event
+    events: Vec<(u64, String, String)>,
+}
+
+impl RecordedNode {
+    fn response_for(&self, path: &str, now: Duration) -> Option<&str> {
+        let now = now.as_millis() as u64;
+        let responses = self.responses.get(path)?;
+        responses
+            .iter()
+            .rev()
+            .find(|(timestamp, _)| *timestamp <= now)
+            .or_else(|| responses.first())
+            .map(|(_, body)| body.as_str())
+    }
+}
+
+/// Replay serves a recording made by a `Recorder` so that a `Monitor` can
+/// run against it as if the recorded nodes were live.
+pub struct Replay {
+    nodes: HashMap<String, RecordedNode>,
+    clock: ReplayClock,
+}
+
+impl Replay {
+    /// Load the recording at `path` to be replayed at `speed` times real time,
+    /// which must be a positive number.
+    pub fn load(path: &Path, speed: f64) -> io::Result<Self> {
+        if !(speed > 0.0 && speed.is_finite()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("replay speed must be a positive number, not {}", speed),
+            ));
+        }
+        let messages = load_recording(path)?;
+        let recording_start = messages
+            .first()
+            .map(|message| Duration::from_millis(message.timestamp))
+            .unwrap_or_default();
+        let mut nodes: HashMap<String, RecordedNode> = HashMap::new();
+        for message in messages {
+            let node = nodes.entry(message.endpoint).or_default();
+            match message.data {
+                RecordedData::Response { path, body } => node
+                    .responses
+                    .entry(path)
+                    .or_default()
+                    .push((message.timestamp, body)),
+                RecordedData::Event { event_type, data } => {
+                    node.events.push((message.timestamp, event_type, data))
+                }
+            }
+        }
+        let clock = ReplayClock {
+            recording_start,
+            replay_start: Instant::now(),
+            speed,
+        };
+        Ok(Self { nodes, clock })
+    }
+
+    /// Return a clock following the time of the recording rather than the wall clock.
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        Arc::new(self.clock)
+    }
+
+    /// Start serving each recorded node, returning a map from each recorded
+    /// endpoint to the endpoint now replaying it.
+    pub fn serve(self) -> HashMap<String, String> {
+        let clock = self.clock;
+        self.nodes
+            .into_iter()
+            .map(|(endpoint, node)| {
+                let node = Arc::new(node);
+                let events = {
+                    let node = node.clone();
+                    warp::get()
+                        .and(warp::path!("eth" / "v1" / "events"))
+                        .map(move || {
+                            let now = clock.now().as_millis() as u64;
+                            let events = node
+                                .events
+                                .iter()
+                                .filter(|(timestamp, _, _)| *timestamp >= now)
+                                .cloned()
+                                .collect::<Vec<_>>();
+                            let events = stream::iter(events).then(
+                                move |(timestamp, event_type, data)| async move {
+                                    clock.sleep_until(Duration::from_millis(timestamp)).await;
+                                    Ok::<_, Infallible>(
+                                        Event::default().event(event_type).data(data),
+                                    )
+                                },
+                            );
+                            warp::sse::reply(warp::sse::keep_alive().stream(events))
+                        })
+                };
+                let responses = warp::get()
+                    .and(warp::path::full())
+                    .map(move |path: FullPath| {
+                        match node.response_for(path.as_str(), clock.now()) {
+                            Some(body) => warp::reply::with_header(
+                                body.to_string(),
+                                "content-type",
+                                "application/json",
+                            )
+                            .into_response(),
+                            None => warp::reply::with_status(
+                                warp::reply::json(&serde_json::json!({
+                                    "code": 404,
+                                    "message": "no response recorded for this path",
+                                })),
+                                StatusCode::NOT_FOUND,
+                            )
+                            .into_response(),
+                        }
+                    });
+                let (addr, server) =
+                    warp::serve(events.or(responses)).bind_ephemeral((REPLAY_HOST, 0));
+                tokio::spawn(server);
+                let replay_endpoint = format!("http://{}", addr);
+                log::info!("replaying node at {} from {}", endpoint, replay_endpoint);
+                (endpoint, replay_endpoint)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_speeds_that_are_not_positive() {
+        let path = Path::new("does-not-exist.jsonl");
+        for speed in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let err = Replay::load(path, speed).err().expect("rejects speed");
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        let err = Replay::load(path, 2.0)
+            .err()
+            .expect("fails to open recording");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,545 @@
+use crate::chain::{Coordinate, FinalityData};
+use crate::clock::Clock;
+use crate::node::Status;
+use eth2::types::{Hash256, Slot};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use thiserror::Error;
+
+const HISTORY_FILE_NAME: &str = "history.jsonl";
+const COMPACTING_FILE_NAME: &str = "history.jsonl.compacting";
+// number of status and finality records kept for each node
+const MAX_RECENT_RECORDS_PER_NODE: usize = 1024;
+// number of heads kept for each node, a little over two days of 12 second slots
+const MAX_HEADS_PER_NODE: usize = 16384;
+// the history file is rewritten with only the records kept once it holds twice as
+// many records as after the last rewrite, and at least this many
+const MIN_RECORDS_BEFORE_COMPACTION: usize = 65536;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadRecord {
+    pub node_id: u64,
+    pub head: Coordinate,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusRecord {
+    pub node_id: u64,
+    pub status: Status,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinalityRecord {
+    pub node_id: u64,
+    pub finality_data: FinalityData,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Record {
+    Head(HeadRecord),
+    Status(StatusRecord),
+    Finality(FinalityRecord),
+}
+
+impl Record {
+    fn timestamp(&self) -> u64 {
+        match self {
+            Record::Head(record) => record.timestamp,
+            Record::Status(record) => record.timestamp,
+            Record::Finality(record) => record.timestamp,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("json error: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+fn push_bounded<T>(records: &mut VecDeque<T>, record: T) {
+    if records.len() == MAX_RECENT_RECORDS_PER_NODE {
+        records.pop_front();
+    }
+    records.push_back(record);
+}
+
+#[derive(Debug, Default)]
+struct NodeHistory {
+    // indexed by slot so that ranges of slots are served from memory
+    heads: BTreeMap<Slot, Vec<HeadRecord>>,
+    head_count: usize,
+    last_head: Option<Hash256>,
+    statuses: VecDeque<StatusRecord>,
+    finality: VecDeque<FinalityRecord>,
+}
+
+impl NodeHistory {
+    fn insert_head(&mut self, record: HeadRecord) {
+        self.last_head = Some(record.head.root);
+        self.heads.entry(record.head.slot).or_default().push(record);
+        self.head_count += 1;
+        if self.head_count > MAX_HEADS_PER_NODE {
+            if let Some(mut oldest) = self.heads.first_entry() {
+                oldest.get_mut().remove(0);
+                if oldest.get().is_empty() {
+                    oldest.remove();
+                }
+                self.head_count -= 1;
+            }
+        }
+    }
+
+    fn records(&self) -> impl Iterator<Item = Record> + '_ {
+        let heads = self.heads.values().flatten().cloned().map(Record::Head);
+        let statuses = self.statuses.iter().cloned().map(Record::Status);
+        let finality = self.finality.iter().cloned().map(Record::Finality);
+        heads.chain(statuses).chain(finality)
+    }
+}
+
+#[derive(Debug, Default)]
+struct StoreInner {
+    recent: HashMap<u64, NodeHistory>,
+    // numbers each record so the writer can skip those already in a compacted file
+    next_sequence: u64,
+}
+
+impl StoreInner {
+    fn insert(&mut self, record: Record) {
+        match record {
+            Record::Head(record) => {
+                let history = self.recent.entry(record.node_id).or_default();
+                history.insert_head(record);
+            }
+            Record::Status(record) => {
+                let history = self.recent.entry(record.node_id).or_default();
+                push_bounded(&mut history.statuses, record);
+            }
+            Record::Finality(record) => {
+                let history = self.recent.entry(record.node_id).or_default();
+                push_bounded(&mut history.finality, record);
+            }
+        }
+    }
+
+    fn records(&self) -> Vec<Record> {
+        self.recent
+            .values()
+            .flat_map(NodeHistory::records)
+            .collect()
+    }
+}
+
+fn compaction_threshold(records_in_file: usize) -> usize {
+    (2 * records_in_file).max(MIN_RECORDS_BEFORE_COMPACTION)
+}
+
+fn open_for_append(path: &Path) -> Result<BufWriter<File>, StoreError> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(BufWriter::new(file))
+}
+
+// replace the history file at `path` with `records`, oldest first
+fn write_records(path: &Path, mut records: Vec<Record>) -> Result<usize, StoreError> {
+    records.sort_by_key(Record::timestamp);
+    let compacting_path = path.with_file_name(COMPACTING_FILE_NAME);
+    let mut writer = BufWriter::new(File::create(&compacting_path)?);
+    for record in &records {
+        serde_json::to_writer(&mut writer, record)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&compacting_path, path)?;
+    Ok(records.len())
+}
+
+fn read_records(path: &Path) -> Result<Vec<Record>, StoreError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    let mut records = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(err) => log::warn!("skipping malformed record in history store: {}", err),
+        }
+    }
+    Ok(records)
+}
+
+// Appends records to the history file on its own thread, so that neither the
+// writes nor a compaction of the file block the callers recording history.
+struct HistoryFile {
+    path: PathBuf,
+    file: BufWriter<File>,
+    records_in_file: usize,
+    compact_at: usize,
+    // records numbered below this are already in the file
+    compacted_before: u64,
+    inner: Arc<Mutex<StoreInner>>,
+}
+
+impl HistoryFile {
+    fn run(mut self, rx: mpsc::Receiver<(u64, Record)>) {
+        while let Ok(message) = rx.recv() {
+            self.append(message);
+            for message in rx.try_iter() {
+                self.append(message);
+            }
+            if let Err(err) = self.file.flush() {
+                log::warn!("could not persist records to history store: {}", err);
+            }
+        }
+    }
+
+    fn append(&mut self, (sequence, record): (u64, Record)) {
+        if sequence < self.compacted_before {
+            return;
+        }
+        let result = serde_json::to_writer(&mut self.file, &record)
+            .map_err(StoreError::from)
+            .and_then(|_| writeln!(self.file).map_err(StoreError::from));
+        match result {
+            Ok(()) => self.records_in_file += 1,
+            Err(err) => log::warn!("could not persist record to history store: {}", err),
+        }
+        if self.records_in_file >= self.compact_at {
+            if let Err(err) = self.compact() {
+                log::warn!("could not compact history store: {}", err);
+                // back off rather than retrying on every record
+                self.compact_at = self.records_in_file * 2;
+            }
+        }
+    }
+
+    // rewrite the history file with only the records kept in memory
+    fn compact(&mut self) -> Result<(), StoreError> {
+        self.file.flush()?;
+        let (records, next_sequence) = {
+            let inner = self.inner.lock().expect("can lock store");
+            (inner.records(), inner.next_sequence)
+        };
+        let records_in_file = write_records(&self.path, records)?;
+        self.file = open_for_append(&self.path)?;
+        self.compacted_before = next_sequence;
+        self.records_in_file = records_in_file;
+        self.compact_at = compaction_threshold(records_in_file);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Writer {
+    tx: Option<mpsc::Sender<(u64, Record)>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Writer {
+    fn send(&self, sequence: u64, record: Record) {
+        let sent = match self.tx {
+            Some(ref tx) => tx.send((sequence, record)).is_ok(),
+            None => false,
+        };
+        if !sent {
+            log::warn!("could not persist record as the history writer has stopped");
+        }
+    }
+}
+
+impl Drop for Writer {
+    // let the writer drain any pending records before the store goes away
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::warn!("history writer panicked");
+            }
+        }
+    }
+}
+
+/// Store keeps the history of each node's heads, status and finality data,
+/// persisting it to disk if given a directory. Queries are served from memory;
+/// the file on disk is only read when the store is opened and is written to
+/// from a dedicated thread.
+#[derive(Debug, Clone)]
+pub struct Store {
+    inner: Arc<Mutex<StoreInner>>,
+    writer: Option<Arc<Writer>>,
+    // timestamps the records
+    clock: Arc<dyn Clock>,
+}
+
+impl Store {
+    /// Open the store under `data_dir`, loading any recent history recorded by a
+    /// previous run. If `data_dir` is `None`, history is only kept in memory.
+    pub fn open(data_dir: Option<&Path>, clock: Arc<dyn Clock>) -> Result<Self, StoreError> {
+        let inner = Arc::new(Mutex::new(StoreInner::default()));
+        let writer = match data_dir {
+            Some(data_dir) => Some(Arc::new(Self::open_writer(data_dir, &inner)?)),
+            None => None,
+        };
+        Ok(Self {
+            inner,
+            writer,
+            clock,
+        })
+    }
+
+    fn open_writer(data_dir: &Path, inner: &Arc<Mutex<StoreInner>>) -> Result<Writer, StoreError> {
+        fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(HISTORY_FILE_NAME);
+        let records = read_records(&path)?;
+        log::info!("loaded {} records from {:?}", records.len(), path);
+        let records = {
+            let mut inner = inner.lock().expect("can lock store");
+            for record in records {
+                inner.insert(record);
+            }
+            inner.records()
+        };
+        // drop anything no longer kept from previous runs
+        let records_in_file = write_records(&path, records)?;
+
+        let history_file = HistoryFile {
+            file: open_for_append(&path)?,
+            path,
+            records_in_file,
+            compact_at: compaction_threshold(records_in_file),
+            compacted_before: 0,
+            inner: inner.clone(),
+        };
+        let (tx, rx) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("history-writer".to_string())
+            .spawn(move || history_file.run(rx))?;
+        Ok(Writer {
+            tx: Some(tx),
+            thread: Some(thread),
+        })
+    }
+
+    fn append(&self, inner: &mut StoreInner, record: Record) {
+        let sequence = inner.next_sequence;
+        inner.next_sequence += 1;
+        if let Some(ref writer) = self.writer {
+            writer.send(sequence, record.clone());
+        }
+        inner.insert(record);
+    }
+
+    pub fn record_head(&self, node_id: u64, head: Coordinate) {
+        let mut inner = self.inner.lock().expect("can lock store");
+        let is_known = inner
+            .recent
+            .get(&node_id)
+            .and_then(|history| history.last_head)
+            == Some(head.root);
+        if is_known {
+            return;
+        }
+        let record = Record::Head(HeadRecord {
+            node_id,
+            head,
+            timestamp: self.clock.now().as_secs(),
+        });
+        self.append(&mut inner, record);
+    }
+
+    pub fn record_status(&self, node_id: u64, status: Status) {
+        let mut inner = self.inner.lock().expect("can lock store");
+        let is_known = inner
+            .recent
+            .get(&node_id)
+            .and_then(|history| history.statuses.back())
+            .is_some_and(|last| last.status == status);
+        if is_known {
+            return;
+        }
+        let record = Record::Status(StatusRecord {
+            node_id,
+            status,
+            timestamp: self.clock.now().as_secs(),
+        });
+        self.append(&mut inner, record);
+    }
+
+    pub fn record_finality(&self, node_id: u64, finality_data: FinalityData) {
+        let mut inner = self.inner.lock().expect("can lock store");
+        let is_known = inner
+            .recent
+            .get(&node_id)
+            .and_then(|history| history.finality.back())
+            .is_some_and(|last| last.finality_data == finality_data);
+        if is_known {
+            return;
+        }
+        let record = Record::Finality(FinalityRecord {
+            node_id,
+            finality_data,
+            timestamp: self.clock.now().as_secs(),
+        });
+        self.append(&mut inner, record);
+    }
+
+    /// Return the heads of the node with `node_id` with a slot in `[start_slot, end_slot]`.
+    pub fn heads(&self, node_id: u64, start_slot: Slot, end_slot: Slot) -> Vec<HeadRecord> {
+        if start_slot > end_slot {
+            return vec![];
+        }
+        let inner = self.inner.lock().expect("can lock store");
+        inner
+            .recent
+            .get(&node_id)
+            .map(|history| {
+                history
+                    .heads
+                    .range(start_slot..=end_slot)
+                    .flat_map(|(_, records)| records)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Return the latest `count` heads of the node with `node_id` by slot, oldest first.
+    pub fn recent_heads(&self, node_id: u64, count: usize) -> Vec<HeadRecord> {
+        let inner = self.inner.lock().expect("can lock store");
+        let mut heads = inner
+            .recent
+            .get(&node_id)
+            .map(|history| {
+                history
+                    .heads
+                    .values()
+                    .rev()
+                    .flat_map(|records| records.iter().rev())
+                    .take(count)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        heads.reverse();
+        heads
+    }
+
+    /// Return the recent status transitions of the node with `node_id`.
+    pub fn statuses(&self, node_id: u64) -> Vec<StatusRecord> {
+        let inner = self.inner.lock().expect("can lock store");
+        inner
+            .recent
+            .get(&node_id)
+            .map(|history| history.statuses.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Return the recent changes in finality of the node with `node_id`.
+    pub fn finality(&self, node_id: u64) -> Vec<FinalityRecord> {
+        let inner = self.inner.lock().expect("can lock store");
+        inner
+            .recent
+            .get(&node_id)
+            .map(|history| history.finality.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SystemClock;
+    use std::process;
+
+    fn head_at(slot: u64) -> Coordinate {
+        Coordinate {
+            slot: Slot::new(slot),
+            root: Hash256::from_low_u64_be(slot),
+        }
+    }
+
+    fn slots_of(heads: &[HeadRecord]) -> Vec<u64> {
+        heads
+            .iter()
+            .map(|record| record.head.slot.as_u64())
+            .collect()
+    }
+
+    #[test]
+    fn serves_heads_by_slot_range_across_restarts() {
+        let data_dir = std::env::temp_dir().join(format!("store-test-{}", process::id()));
+        let _ = fs::remove_dir_all(&data_dir);
+
+        let store = Store::open(Some(&data_dir), Arc::new(SystemClock)).expect("can open store");
+        for slot in 1..=10 {
+            store.record_head(1, head_at(slot));
+        }
+        store.record_head(2, head_at(5));
+        assert_eq!(
+            slots_of(&store.heads(1, Slot::new(3), Slot::new(6))),
+            vec![3, 4, 5, 6]
+        );
+        assert!(store.heads(1, Slot::new(6), Slot::new(3)).is_empty());
+        assert_eq!(slots_of(&store.recent_heads(1, 3)), vec![8, 9, 10]);
+        drop(store);
+
+        let store = Store::open(Some(&data_dir), Arc::new(SystemClock)).expect("can reopen store");
+        assert_eq!(
+            slots_of(&store.heads(1, Slot::new(0), Slot::new(u64::MAX))),
+            (1..=10).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            slots_of(&store.heads(2, Slot::new(0), Slot::new(u64::MAX))),
+            vec![5]
+        );
+
+        fs::remove_dir_all(&data_dir).expect("can clean up");
+    }
+
+    #[test]
+    fn compacts_without_duplicating_records() {
+        let data_dir = std::env::temp_dir().join(format!("store-compact-test-{}", process::id()));
+        let _ = fs::remove_dir_all(&data_dir);
+
+        // enough heads across nodes to compact the file while recording
+        let node_count = 5;
+        let heads_per_node = MAX_HEADS_PER_NODE as u64;
+        let store = Store::open(Some(&data_dir), Arc::new(SystemClock)).expect("can open store");
+        for slot in 1..=heads_per_node {
+            for node_id in 0..node_count {
+                store.record_head(node_id, head_at(slot));
+            }
+        }
+        drop(store);
+
+        let records = read_records(&data_dir.join(HISTORY_FILE_NAME)).expect("can read records");
+        assert_eq!(records.len() as u64, node_count * heads_per_node);
+        let store = Store::open(Some(&data_dir), Arc::new(SystemClock)).expect("can reopen store");
+        for node_id in 0..node_count {
+            assert_eq!(
+                slots_of(&store.heads(node_id, Slot::new(0), Slot::new(u64::MAX))),
+                (1..=heads_per_node).collect::<Vec<_>>()
+            );
+        }
+
+        fs::remove_dir_all(&data_dir).expect("can clean up");
+    }
+}
@@ -1,39 +1,174 @@
-// use eth2::types::Epoch;
-use slot_clock::{Slot, SlotClock, SystemTimeSlotClock};
-use tokio::time::Duration;
+use crate::clock::Clock;
+use eth2::types::{Epoch, Slot};
+use std::sync::Arc;
+use std::time::Duration;
 
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+#[derive(Clone)]
 pub struct Timer {
-    _inner: SystemTimeSlotClock,
-    _slots_per_epoch: u64,
+    clock: Arc<dyn Clock>,
+    genesis: Duration,
+    slot_duration: Duration,
+    slots_per_epoch: u64,
 }
 
 impl Timer {
-    pub fn new(genesis_time: u64, seconds_per_slot: u64, slots_per_epoch: u64) -> Self {
-        let genesis = Duration::from_secs(genesis_time);
-        let slot_duration = Duration::from_secs(seconds_per_slot);
-        let genesis_slot = Slot::new(0);
-        let inner = SystemTimeSlotClock::new(genesis_slot, genesis, slot_duration);
+    pub fn new(
+        clock: Arc<dyn Clock>,
+        genesis: Duration,
+        slot_duration: Duration,
+        slots_per_epoch: u64,
+    ) -> Self {
         Self {
-            _inner: inner,
-            _slots_per_epoch: slots_per_epoch,
+            clock,
+            genesis,
+            slot_duration,
+            slots_per_epoch,
         }
     }
 
-    // pub fn is_before_genesis(&self) -> bool {
-    //     self.inner
-    //         .is_prior_to_genesis()
-    //         .expect("can read the system clock")
-    // }
+    pub fn is_before_genesis(&self) -> bool {
+        self.clock.now() < self.genesis
+    }
 
-    // pub async fn tick_slot(&self) -> (Slot, Epoch) {
-    //     let next_slot_duration = self
-    //         .inner
-    //         .duration_to_next_slot()
-    //         .expect("can read system clock");
+    pub fn current_slot(&self) -> Option<Slot> {
+        let since_genesis = self.clock.now().checked_sub(self.genesis)?;
+        let slot = since_genesis.as_nanos() / self.slot_duration.as_nanos();
+        Some(Slot::new(slot as u64))
+    }
+
+    /// Return how many slots `slot` is behind the current slot, if after genesis.
+    pub fn slots_behind(&self, slot: Slot) -> Option<u64> {
+        self.current_slot()
+            .map(|current_slot| current_slot.as_u64().saturating_sub(slot.as_u64()))
+    }
+
+    // `None` if the start of `slot` is past what a `Duration` can hold
+    fn start_of(&self, slot: Slot) -> Option<Duration> {
+        let nanos = self
+            .slot_duration
+            .as_nanos()
+            .checked_mul(u128::from(slot.as_u64()))?;
+        let since_genesis = Duration::new(
+            u64::try_from(nanos / NANOS_PER_SEC).ok()?,
+            (nanos % NANOS_PER_SEC) as u32,
+        );
+        self.genesis.checked_add(since_genesis)
+    }
+
+    pub async fn wait_for_genesis(&self) {
+        self.clock.sleep_until(self.genesis).await;
+    }
 
-    //     sleep(next_slot_duration).await;
-    //     let slot = self.inner.now().expect("can read system clock");
-    //     let epoch = slot.epoch(self.slots_per_epoch);
-    //     (slot, epoch)
-    // }
+    pub async fn tick_slot(&self) -> (Slot, Epoch) {
+        let next_slot = match self.current_slot() {
+            Some(slot) => slot + 1,
+            None => Slot::new(0),
+        };
+        match self.start_of(next_slot) {
+            Some(start) => self.clock.sleep_until(start).await,
+            None => std::future::pending().await,
+        }
+
+        let slot = self.current_slot().expect("is after genesis");
+        let epoch = slot.epoch(self.slots_per_epoch);
+        (slot, epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use tokio::time::timeout;
+
+    const GENESIS: Duration = Duration::from_secs(1_000);
+    const SLOT_DURATION: Duration = Duration::from_secs(12);
+    const SLOTS_PER_EPOCH: u64 = 32;
+    // generous bound on anything that should complete as soon as the clock moves
+    const WAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn timer_at(now: Duration) -> (Timer, ManualClock) {
+        let clock = ManualClock::new(now);
+        let timer = Timer::new(
+            Arc::new(clock.clone()),
+            GENESIS,
+            SLOT_DURATION,
+            SLOTS_PER_EPOCH,
+        );
+        (timer, clock)
+    }
+
+    #[test]
+    fn counts_slots_behind_the_clock() {
+        let (timer, clock) = timer_at(GENESIS - Duration::from_secs(1));
+        assert!(timer.is_before_genesis());
+        assert_eq!(timer.slots_behind(Slot::new(0)), None);
+
+        clock.set(GENESIS + SLOT_DURATION * 10 + Duration::from_secs(3));
+        assert_eq!(timer.current_slot(), Some(Slot::new(10)));
+        assert_eq!(timer.slots_behind(Slot::new(7)), Some(3));
+        assert_eq!(timer.slots_behind(Slot::new(10)), Some(0));
+        assert_eq!(timer.slots_behind(Slot::new(12)), Some(0));
+    }
+
+    #[tokio::test]
+    async fn waits_for_genesis() {
+        let (timer, clock) = timer_at(GENESIS - SLOT_DURATION);
+        let wait = timer.wait_for_genesis();
+        tokio::pin!(wait);
+        assert!(futures::poll!(&mut wait).is_pending());
+
+        clock.advance(SLOT_DURATION / 2);
+        assert!(futures::poll!(&mut wait).is_pending());
+
+        clock.set(GENESIS);
+        timeout(WAKE_TIMEOUT, wait)
+            .await
+            .expect("wakes up at genesis");
+        assert!(!timer.is_before_genesis());
+    }
+
+    #[tokio::test]
+    async fn ticks_at_the_start_of_each_slot() {
+        let (timer, clock) = timer_at(GENESIS + SLOT_DURATION * 31 + Duration::from_secs(5));
+        let tick = timer.tick_slot();
+        tokio::pin!(tick);
+        assert!(futures::poll!(&mut tick).is_pending());
+
+        clock.set(GENESIS + SLOT_DURATION * 32 - Duration::from_millis(1));
+        assert!(futures::poll!(&mut tick).is_pending());
+
+        clock.set(GENESIS + SLOT_DURATION * 32);
+        let (slot, epoch) = timeout(WAKE_TIMEOUT, tick)
+            .await
+            .expect("ticks at the next slot");
+        assert_eq!(slot, Slot::new(32));
+        assert_eq!(epoch, Epoch::new(1));
+    }
+
+    #[tokio::test]
+    async fn ticks_slot_zero_from_before_genesis() {
+        let (timer, clock) = timer_at(GENESIS - SLOT_DURATION);
+        let tick = timer.tick_slot();
+        tokio::pin!(tick);
+        assert!(futures::poll!(&mut tick).is_pending());
+
+        clock.set(GENESIS);
+        let (slot, epoch) = timeout(WAKE_TIMEOUT, tick).await.expect("ticks at genesis");
+        assert_eq!(slot, Slot::new(0));
+        assert_eq!(epoch, Epoch::new(0));
+    }
+
+    #[test]
+    fn computes_the_start_of_slots_past_u32() {
+        let (timer, _) = timer_at(GENESIS);
+        let slot = u64::from(u32::MAX) + 2;
+        assert_eq!(
+            timer.start_of(Slot::new(slot)),
+            Some(GENESIS + Duration::from_secs(slot * SLOT_DURATION.as_secs()))
+        );
+        assert_eq!(timer.start_of(Slot::new(u64::MAX)), None);
+    }
 }